@@ -0,0 +1,194 @@
+use crate::interpreter::Exception;
+use crate::tokenizer::{reverse_keyword, reverse_symbol, ParseError, Span, Token, TokenizeError, TokenizeErrorKind};
+
+/// An error to show to the user: its kind like `SyntaxError` or `TypeError`,
+/// the message, where it happened when it's known, and hints to fix it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    pub kind: String,
+    pub message: String,
+    pub span: Option<Span>,
+    pub hints: Vec<String>,
+}
+
+// Token as it's named in a message, like "':'" or "name 'x'"
+pub fn describe_token(token: &Token) -> String {
+    match token {
+        Token::Identifier(name) => format!("name '{}'", name),
+        Token::Keyword(keyword) => format!("keyword '{}'", reverse_keyword(keyword)),
+        Token::Symbol(symbol) => format!("'{}'", reverse_symbol(symbol)),
+        Token::Number(_) => "number".to_string(),
+        Token::String(_) | Token::Bytes(_) | Token::FString(_) => "string".to_string(),
+        Token::Comment(_) => "comment".to_string(),
+        Token::LineBreak | Token::SoftLineBreak => "newline".to_string(),
+        Token::LineContinuation => "'\\'".to_string(),
+        Token::Indent => "indent".to_string(),
+        Token::Dedent => "dedent".to_string(),
+        Token::Space => "whitespace".to_string(),
+        Token::CarriageReturn => "carriage return".to_string(),
+    }
+}
+
+impl Diagnostic {
+    pub fn new(kind: &str, message: impl Into<String>, span: Option<Span>) -> Self {
+        Diagnostic { kind: kind.to_string(), message: message.into(), span, hints: Vec::new() }
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hints.push(hint.into());
+        self
+    }
+
+    /// The diagnostic with the line of the source it points at, like
+    ///
+    /// ```text
+    /// SyntaxError: expected ':', found newline
+    ///   --> script.py:1:11
+    ///    |
+    ///  1 | while True
+    ///    |           ^
+    ///    = hint: ...
+    /// ```
+    pub fn render(&self, file: &str, source: &str) -> String {
+        let mut output = match self.message.as_str() {
+            "" => format!("{}\n", self.kind),
+            message => format!("{}: {}\n", self.kind, message),
+        };
+        let Some(span) = self.span else {
+            output.push_str(&format!("  --> {}\n", file));
+            for hint in &self.hints {
+                output.push_str(&format!("   = hint: {}\n", hint));
+            }
+            return output;
+        };
+        let number = span.line.to_string();
+        let gutter = " ".repeat(number.len());
+        output.push_str(&format!("{}--> {}:{}:{}\n", gutter, file, span.line, span.column));
+        output.push_str(&format!("{} |\n", gutter));
+
+        let line = source.lines().nth(span.line.saturating_sub(1)).unwrap_or_default();
+        output.push_str(&format!("{} | {}\n", number, line));
+        // tabs are kept so the marker lines up with the text above it
+        let before: String = line
+            .chars()
+            .take(span.column.saturating_sub(1))
+            .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+            .collect();
+        // a span over several lines is underlined up to the end of its first line
+        let line_len = line.chars().count();
+        let end = if span.end_line == span.line { span.end_column } else { line_len + 1 };
+        let width = end.min(line_len + 1).saturating_sub(span.column).max(1);
+        output.push_str(&format!("{} | {}^{}\n", gutter, before, "~".repeat(width - 1)));
+
+        for hint in &self.hints {
+            output.push_str(&format!("{} = hint: {}\n", gutter, hint));
+        }
+        output
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        let span = Some(error.span());
+        match error {
+            ParseError::UnexpectedToken(Token::Indent, _) => Diagnostic::new("IndentationError", "unexpected indent", span)
+                .with_hint("this line is indented more than the line before it"),
+            ParseError::UnexpectedToken(token, _) => {
+                Diagnostic::new("SyntaxError", format!("unexpected {}", describe_token(token)), span)
+            }
+            ParseError::ExpectedToken(expected, _, _) if expected == "indent" => {
+                Diagnostic::new("IndentationError", "expected an indented block", span)
+                    .with_hint("the lines of a block are indented more than the line ending with ':'")
+            }
+            ParseError::ExpectedToken(expected, found, _) => {
+                let diagnostic = Diagnostic::new("SyntaxError", format!("expected {}, found {}", expected, describe_token(found)), span);
+                match (expected.as_str(), found) {
+                    ("':'", _) => diagnostic.with_hint("`def`, `if`, `elif`, `else`, `while`, `for`, `match` and `case` end their line with ':'"),
+                    (_, Token::Symbol(symbol)) if matches!(reverse_symbol(symbol), ")" | "]" | "}") => {
+                        diagnostic.with_hint("check that the brackets before it are balanced")
+                    }
+                    _ => diagnostic,
+                }
+            }
+            ParseError::InvalidSyntax(message, _) => Diagnostic::new("SyntaxError", message.clone(), span),
+            ParseError::UnexpectedEndOfInput(_) => Diagnostic::new("SyntaxError", "unexpected end of input", span),
+            ParseError::RecursionLimitExceeded(_) => Diagnostic::new("SyntaxError", "too many nested expressions or blocks", span),
+        }
+    }
+}
+
+impl From<&TokenizeError> for Diagnostic {
+    fn from(error: &TokenizeError) -> Self {
+        let span = Some(error.span);
+        let message = error.message.as_str();
+        match error.kind {
+            TokenizeErrorKind::InconsistentTabs => {
+                Diagnostic::new("TabError", message, span).with_hint("indent with spaces only, 4 for each level")
+            }
+            TokenizeErrorKind::BadDedent => Diagnostic::new("IndentationError", message, span)
+                .with_hint("the line must be indented like one of the blocks it closes"),
+            TokenizeErrorKind::UnmatchedBracket => {
+                Diagnostic::new("SyntaxError", message, span).with_hint("this bracket closes nothing, remove it or open one before")
+            }
+            TokenizeErrorKind::UnclosedBracket => {
+                Diagnostic::new("SyntaxError", message, span).with_hint("add the closing bracket where the expression ends")
+            }
+            TokenizeErrorKind::MismatchedBracket => {
+                Diagnostic::new("SyntaxError", message, span).with_hint("brackets close in the reverse order they were opened")
+            }
+            TokenizeErrorKind::InvalidToken => Diagnostic::new("SyntaxError", message, span),
+        }
+    }
+}
+
+// a runtime error points at the statement that raised it
+impl From<&Exception> for Diagnostic {
+    fn from(exception: &Exception) -> Self {
+        Diagnostic::new(&exception.kind, exception.message.clone(), exception.span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::{tokenize_with, TokenizerOptions};
+
+    // the kind and hint of the diagnostic for the first error of the source
+    fn first_error(source: &str) -> (TokenizeErrorKind, Diagnostic) {
+        let (_, errors) = tokenize_with(source, TokenizerOptions { recover_errors: true, ..Default::default() });
+        let error = errors.first().expect("no tokenize error");
+        (error.kind, Diagnostic::from(error))
+    }
+
+    #[test]
+    fn tokenize_errors_by_kind() {
+        let (kind, diagnostic) = first_error("if x:\n\tif y:\n        pass\n");
+        assert_eq!((kind, diagnostic.kind.as_str()), (TokenizeErrorKind::InconsistentTabs, "TabError"));
+        let (kind, diagnostic) = first_error("if x:\n    y\n  z\n");
+        assert_eq!((kind, diagnostic.kind.as_str()), (TokenizeErrorKind::BadDedent, "IndentationError"));
+        assert_eq!(first_error("x)\n").0, TokenizeErrorKind::UnmatchedBracket);
+        assert_eq!(first_error("(x]\n").0, TokenizeErrorKind::MismatchedBracket);
+        assert_eq!(first_error("f(x\n").0, TokenizeErrorKind::UnclosedBracket);
+        let (kind, diagnostic) = first_error("x = $\n");
+        assert_eq!(kind, TokenizeErrorKind::InvalidToken);
+        assert!(diagnostic.hints.is_empty());
+    }
+
+    #[test]
+    fn recursion_limit_message() {
+        let diagnostic = Diagnostic::from(&ParseError::RecursionLimitExceeded(Span::default()));
+        assert_eq!(diagnostic.message, "too many nested expressions or blocks");
+    }
+
+    #[test]
+    fn render_points_at_the_span() {
+        let source = "while True\n    pass\n";
+        let (tokens, _) = tokenize_with(source, TokenizerOptions::default());
+        let error = crate::parser::Parser::new(tokens).parse_tokens().unwrap_err();
+        let expected = "SyntaxError: expected ':', found newline\n --> t.py:1:11\n  |\n1 | while True\n  |           ^\n  = hint: `def`, `if`, `elif`, `else`, `while`, `for`, `match` and `case` end their line with ':'\n";
+        assert_eq!(Diagnostic::from(&error).render("t.py", source), expected);
+        // without a span only the file is shown
+        let exception = Exception::new("ZeroDivisionError", "division by zero");
+        assert_eq!(Diagnostic::from(&exception).render("t.py", source), "ZeroDivisionError: division by zero\n  --> t.py\n");
+    }
+}
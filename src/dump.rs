@@ -0,0 +1,178 @@
+use crate::interpreter::Value;
+use crate::source::Source;
+use crate::tokenizer::{tokenize_with, SpannedToken, Symbol, Token, TokenizeError, TokenizerOptions};
+
+// Name of the token in the CPython `token` module. Whitespace and line
+// continuations don't appear in CPython's token stream.
+fn token_type(token: &Token, exact: bool) -> Option<&'static str> {
+    match token {
+        Token::Identifier(_) | Token::Keyword(_) => Some("NAME"),
+        Token::Number(_) => Some("NUMBER"),
+        Token::String(_) | Token::Bytes(_) | Token::FString(_) => Some("STRING"),
+        Token::Symbol(symbol) if exact => Some(exact_type(symbol)),
+        Token::Symbol(_) => Some("OP"),
+        Token::Comment(_) => Some("COMMENT"),
+        Token::LineBreak => Some("NEWLINE"),
+        Token::SoftLineBreak => Some("NL"),
+        Token::Indent => Some("INDENT"),
+        Token::Dedent => Some("DEDENT"),
+        Token::Space | Token::LineContinuation | Token::CarriageReturn => None,
+    }
+}
+
+// operators get their own names with `-e`, like `python -m tokenize -e`
+fn exact_type(symbol: &Symbol) -> &'static str {
+    match symbol {
+        Symbol::LeftParen => "LPAR",
+        Symbol::RightParen => "RPAR",
+        Symbol::LeftBracket => "LSQB",
+        Symbol::RightBracket => "RSQB",
+        Symbol::LeftCurlyBrace => "LBRACE",
+        Symbol::RightCurlyBrace => "RBRACE",
+        Symbol::Comma => "COMMA",
+        Symbol::Dot => "DOT",
+        Symbol::Ellipsis => "ELLIPSIS",
+        Symbol::Colon => "COLON",
+        Symbol::SemiColon => "SEMI",
+        Symbol::Arrow => "RARROW",
+        Symbol::Walrus => "COLONEQUAL",
+        Symbol::Assign => "EQUAL",
+        Symbol::Plus => "PLUS",
+        Symbol::Minus => "MINUS",
+        Symbol::Multiply => "STAR",
+        Symbol::Divide => "SLASH",
+        Symbol::FloorDivide => "DOUBLESLASH",
+        Symbol::Modulo => "PERCENT",
+        Symbol::Exponent => "DOUBLESTAR",
+        Symbol::MatrixMultiply => "AT",
+        Symbol::LessThan => "LESS",
+        Symbol::GreaterThan => "GREATER",
+        Symbol::LessThanOrEqual => "LESSEQUAL",
+        Symbol::GreaterThanOrEqual => "GREATEREQUAL",
+        Symbol::Equal => "EQEQUAL",
+        Symbol::NotEqual => "NOTEQUAL",
+        Symbol::BitwiseAnd => "AMPER",
+        Symbol::BitwiseOr => "VBAR",
+        Symbol::BitwiseXor => "CIRCUMFLEX",
+        Symbol::BitwiseNot => "TILDE",
+        Symbol::ShiftLeft => "LEFTSHIFT",
+        Symbol::ShiftRight => "RIGHTSHIFT",
+        Symbol::PlusAssign => "PLUSEQUAL",
+        Symbol::MinusAssign => "MINEQUAL",
+        Symbol::MultiplyAssign => "STAREQUAL",
+        Symbol::DivideAssign => "SLASHEQUAL",
+        Symbol::FloorDivideAssign => "DOUBLESLASHEQUAL",
+        Symbol::ModuloAssign => "PERCENTEQUAL",
+        Symbol::ExponentAssign => "DOUBLESTAREQUAL",
+        Symbol::MatrixMultiplyAssign => "ATEQUAL",
+        Symbol::BitwiseAndAssign => "AMPEREQUAL",
+        Symbol::BitwiseOrAssign => "VBAREQUAL",
+        Symbol::BitwiseXorAssign => "CIRCUMFLEXEQUAL",
+        Symbol::ShiftLeftAssign => "LEFTSHIFTEQUAL",
+        Symbol::ShiftRightAssign => "RIGHTSHIFTEQUAL",
+    }
+}
+
+// one line of output, rows start at 1 and columns at 0 like in CPython
+fn dump_line(start: (usize, usize), end: (usize, usize), name: &str, text: &str) -> String {
+    let range = format!("{},{}-{},{}:", start.0, start.1, end.0, end.1);
+    format!("{:<20}{:<15}{:<15}\n", range, name, Value::String(text.to_string()).repr())
+}
+
+/// Tokens of the source in the format of `python -m tokenize`, or with `exact`
+/// of `python -m tokenize -e`. The output stops at the first error, which is
+/// returned with the lines printed before it.
+pub fn dump_tokens(source: &Source, exact: bool) -> (String, Option<TokenizeError>) {
+    let options = TokenizerOptions { keep_trivia: true, ..Default::default() };
+    let (tokens, errors) = tokenize_with(&source.text, options);
+    let mut output = dump_line((0, 0), (0, 0), "ENCODING", source.encoding.name());
+
+    // the end of the input is on the line after the last one
+    let text = &source.text;
+    let lines = text.matches('\n').count() + usize::from(!text.is_empty() && !text.ends_with('\n'));
+    let end_of_input = (lines + 1, 0);
+    let input_len = text.len();
+
+    let mut previous: Option<&SpannedToken> = None;
+    for spanned in &tokens {
+        let Some(name) = token_type(&spanned.token, exact) else {
+            previous = Some(spanned);
+            continue;
+        };
+        let span = spanned.span;
+        let raw = spanned.raw.as_deref().unwrap_or_default();
+        let mut start = (span.line, span.column - 1);
+        let mut end = (span.end_line, span.end_column - 1);
+        let mut text = raw;
+        match spanned.token {
+            // the indentation is the text of the INDENT
+            Token::Indent => {
+                start.1 = 0;
+                if let Some(SpannedToken { token: Token::Space, span: space, raw: Some(raw) }) = previous {
+                    if space.column == 1 && space.line == span.line {
+                        text = raw;
+                    }
+                }
+            }
+            // line breaks end on their own line, even the empty NEWLINE of a last
+            // line without a line break is one column wide
+            Token::LineBreak | Token::SoftLineBreak => end = (start.0, start.1 + 1),
+            Token::Dedent if span.start == input_len => {
+                start = end_of_input;
+                end = end_of_input;
+            }
+            _ => {}
+        }
+        output.push_str(&dump_line(start, end, name, text));
+        previous = Some(spanned);
+    }
+
+    if let Some(error) = errors.into_iter().next() {
+        return (output, Some(error));
+    }
+    output.push_str(&dump_line(end_of_input, end_of_input, "ENDMARKER", ""));
+    (output, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::source::decode_source;
+
+    #[test]
+    fn dump_matches_cpython() {
+        // `python3 -m tokenize -e` on the same source
+        let expected = [
+            "0,0-0,0:            ENCODING       'utf-8'        ",
+            "1,0-1,2:            NAME           'if'           ",
+            "1,3-1,4:            NAME           'x'            ",
+            "1,4-1,5:            COLON          ':'            ",
+            "1,7-1,10:           COMMENT        '# c'          ",
+            "1,10-1,11:          NEWLINE        '\\n'           ",
+            "2,0-2,1:            NL             '\\n'           ",
+            "3,0-3,4:            INDENT         '    '         ",
+            "3,4-3,5:            NAME           'y'            ",
+            "3,6-3,7:            EQUAL          '='            ",
+            "3,8-3,9:            LSQB           '['            ",
+            "3,9-3,10:           NUMBER         '1'            ",
+            "3,10-3,11:          COMMA          ','            ",
+            "3,11-3,12:          NL             '\\n'           ",
+            "4,9-4,10:           NUMBER         '2'            ",
+            "4,10-4,11:          RSQB           ']'            ",
+            "4,11-4,12:          NEWLINE        '\\n'           ",
+            "5,0-5,0:            DEDENT         ''             ",
+            "5,0-5,1:            NAME           'z'            ",
+            "5,2-5,4:            ATEQUAL        '@='           ",
+            "5,5-5,6:            NUMBER         '1'            ",
+            "5,6-5,7:            NEWLINE        '\\n'           ",
+            "6,0-6,0:            ENDMARKER      ''             ",
+        ];
+        let source = decode_source(b"if x:  # c\n\n    y = [1,\n         2]\nz @= 1\n").unwrap();
+        let (output, error) = dump_tokens(&source, true);
+        assert_eq!(error, None);
+        assert_eq!(output.lines().collect::<Vec<_>>(), expected);
+        // without `-e` the operators are all OP
+        let (output, _) = dump_tokens(&source, false);
+        assert!(output.lines().any(|line| line.starts_with("5,2-5,4:") && line.contains("OP ")));
+    }
+}
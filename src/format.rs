@@ -0,0 +1,309 @@
+use crate::interpreter::Value;
+
+// Format specification mini-language, used by f-strings and `format()`:
+// [[fill]align][sign]["z"]["#"]["0"][width][grouping]["." precision][type]
+#[derive(Debug)]
+struct FormatSpec {
+    fill: char,
+    align: Option<char>,
+    sign: char,
+    // `z` turns negative zero into positive zero after rounding
+    coerce_zero: bool,
+    alternate: bool,
+    width: usize,
+    grouping: Option<char>,
+    precision: Option<usize>,
+    kind: Option<char>,
+}
+
+fn is_align(ch: char) -> bool {
+    matches!(ch, '<' | '>' | '^' | '=')
+}
+
+fn read_number(chars: &[char], pos: &mut usize) -> Option<usize> {
+    let start = *pos;
+    while *pos < chars.len() && chars[*pos].is_ascii_digit() {
+        *pos += 1;
+    }
+    if start == *pos {
+        return None;
+    }
+    chars[start..*pos].iter().collect::<String>().parse().ok()
+}
+
+fn parse_spec(spec: &str) -> Result<FormatSpec, String> {
+    let chars: Vec<char> = spec.chars().collect();
+    let mut format = FormatSpec {
+        fill: ' ',
+        align: None,
+        sign: '-',
+        coerce_zero: false,
+        alternate: false,
+        width: 0,
+        grouping: None,
+        precision: None,
+        kind: None,
+    };
+    let mut pos = 0;
+
+    if chars.len() >= 2 && is_align(chars[1]) {
+        format.fill = chars[0];
+        format.align = Some(chars[1]);
+        pos = 2;
+    } else if !chars.is_empty() && is_align(chars[0]) {
+        format.align = Some(chars[0]);
+        pos = 1;
+    }
+    if let Some(&sign @ ('+' | '-' | ' ')) = chars.get(pos) {
+        format.sign = sign;
+        pos += 1;
+    }
+    if chars.get(pos) == Some(&'z') {
+        format.coerce_zero = true;
+        pos += 1;
+    }
+    if chars.get(pos) == Some(&'#') {
+        format.alternate = true;
+        pos += 1;
+    }
+    // a leading zero in the width pads numbers with zeros after the sign
+    if chars.get(pos) == Some(&'0') {
+        if format.align.is_none() {
+            format.fill = '0';
+            format.align = Some('=');
+        }
+        pos += 1;
+    }
+    format.width = read_number(&chars, &mut pos).unwrap_or(0);
+    if let Some(&grouping @ (',' | '_')) = chars.get(pos) {
+        format.grouping = Some(grouping);
+        pos += 1;
+    }
+    if chars.get(pos) == Some(&'.') {
+        pos += 1;
+        format.precision = Some(read_number(&chars, &mut pos).ok_or("Format specifier missing precision")?);
+    }
+    if pos < chars.len() {
+        format.kind = Some(chars[pos]);
+        pos += 1;
+    }
+    if pos < chars.len() {
+        return Err("Invalid format specifier".to_string());
+    }
+    Ok(format)
+}
+
+// insert the grouping char every `size` digits, counting from the right
+fn group_digits(digits: &str, separator: char, size: usize) -> String {
+    let mut grouped = String::new();
+    for (index, ch) in digits.chars().enumerate() {
+        if index > 0 && (digits.len() - index).is_multiple_of(size) {
+            grouped.push(separator);
+        }
+        grouped.push(ch);
+    }
+    grouped
+}
+
+// Pad the formatted value up to the width. The sign and prefix like `0x` are
+// kept before the padding when the alignment is `=`.
+fn pad(prefix: &str, body: &str, format: &FormatSpec, default_align: char) -> String {
+    let len = prefix.chars().count() + body.chars().count();
+    let padding = format.width.saturating_sub(len);
+    let fill = |count: usize| format.fill.to_string().repeat(count);
+    match format.align.unwrap_or(default_align) {
+        '<' => format!("{}{}{}", prefix, body, fill(padding)),
+        '^' => format!("{}{}{}{}", fill(padding / 2), prefix, body, fill(padding - padding / 2)),
+        '=' => format!("{}{}{}", prefix, fill(padding), body),
+        _ => format!("{}{}{}", fill(padding), prefix, body),
+    }
+}
+
+fn sign_prefix(negative: bool, format: &FormatSpec) -> &'static str {
+    match (negative, format.sign) {
+        (true, _) => "-",
+        (false, '+') => "+",
+        (false, ' ') => " ",
+        _ => "",
+    }
+}
+
+fn format_integer(value: i64, format: &FormatSpec) -> Result<String, String> {
+    let kind = format.kind.unwrap_or('d');
+    if let 'e' | 'E' | 'f' | 'F' | 'g' | 'G' | '%' = kind {
+        return format_float(value as f64, format);
+    }
+    if format.precision.is_some() {
+        return Err("Precision not allowed in integer format specifier".to_string());
+    }
+    let magnitude = value.unsigned_abs();
+    let (digits, radix_prefix, group_size) = match kind {
+        'd' | 'n' => (magnitude.to_string(), "", 3),
+        'b' => (format!("{:b}", magnitude), "0b", 4),
+        'o' => (format!("{:o}", magnitude), "0o", 4),
+        'x' => (format!("{:x}", magnitude), "0x", 4),
+        'X' => (format!("{:X}", magnitude), "0X", 4),
+        'c' => {
+            let ch = u32::try_from(value).ok().and_then(char::from_u32).ok_or("%c arg not in range(0x110000)")?;
+            return Ok(pad("", &ch.to_string(), format, '>'));
+        }
+        _ => return Err(format!("Unknown format code '{}' for object of type 'int'", kind)),
+    };
+    let digits = match format.grouping {
+        Some(',') if group_size == 4 => return Err(format!("Cannot specify ',' with '{}'.", kind)),
+        Some(separator) => group_digits(&digits, separator, group_size),
+        None => digits,
+    };
+    let mut prefix = sign_prefix(value < 0, format).to_string();
+    if format.alternate {
+        prefix.push_str(radix_prefix);
+    }
+    Ok(pad(&prefix, &digits, format, '>'))
+}
+
+// Rust writes exponents like `e-7`, Python always uses a sign and two digits.
+fn python_exponent(formatted: &str, upper: bool) -> String {
+    let (mantissa, exponent) = formatted.split_once('e').unwrap();
+    let exponent: i32 = exponent.parse().unwrap();
+    let sign = if exponent < 0 { '-' } else { '+' };
+    let e = if upper { 'E' } else { 'e' };
+    format!("{}{}{}{:02}", mantissa, e, sign, exponent.abs())
+}
+
+fn strip_trailing_zeros(digits: &str) -> String {
+    match digits.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) if mantissa.contains('.') => {
+            let e = &digits[mantissa.len()..mantissa.len() + 1];
+            format!("{}{}{}", mantissa.trim_end_matches('0').trim_end_matches('.'), e, exponent)
+        }
+        None if digits.contains('.') => digits.trim_end_matches('0').trim_end_matches('.').to_string(),
+        _ => digits.to_string(),
+    }
+}
+
+// General format: fixed point or scientific notation depending on the exponent.
+fn general_format(value: f64, precision: usize, upper: bool, keep_zeros: bool) -> String {
+    let precision = precision.max(1);
+    let scientific = format!("{:.*e}", precision - 1, value);
+    let exponent: i32 = scientific.split_once('e').unwrap().1.parse().unwrap();
+    let formatted = if -4 <= exponent && exponent < precision as i32 {
+        format!("{:.*}", (precision as i32 - 1 - exponent) as usize, value)
+    } else {
+        python_exponent(&scientific, upper)
+    };
+    if keep_zeros {
+        formatted
+    } else {
+        strip_trailing_zeros(&formatted)
+    }
+}
+
+/// Shortest representation of a float that reads back to the same value, the
+/// same way Python prints floats.
+pub fn float_repr(value: f64) -> String {
+    if value.is_nan() {
+        return "nan".to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    let scientific = format!("{:e}", value);
+    let exponent: i32 = scientific.split_once('e').unwrap().1.parse().unwrap();
+    if (-4..16).contains(&exponent) {
+        let fixed = format!("{}", value);
+        if fixed.contains('.') {
+            fixed
+        } else {
+            format!("{}.0", fixed)
+        }
+    } else {
+        python_exponent(&scientific, false)
+    }
+}
+
+fn format_float(value: f64, format: &FormatSpec) -> Result<String, String> {
+    let mut value = value;
+    let upper = matches!(format.kind, Some('E' | 'F' | 'G'));
+    let body = if value.is_nan() || value.is_infinite() {
+        let text = if value.is_nan() { "nan" } else { "inf" };
+        let text = if upper { text.to_uppercase() } else { text.to_string() };
+        if format.kind == Some('%') { text + "%" } else { text }
+    } else {
+        let magnitude = value.abs();
+        let body = match format.kind {
+            None => match format.precision {
+                None => float_repr(magnitude),
+                // like `g`, but fixed point keeps at least one decimal digit
+                Some(precision) => {
+                    let formatted = general_format(magnitude, precision, false, format.alternate);
+                    if formatted.contains(['.', 'e']) {
+                        formatted
+                    } else {
+                        format!("{}.0", formatted)
+                    }
+                }
+            },
+            Some('f' | 'F') => format!("{:.*}", format.precision.unwrap_or(6), magnitude),
+            Some('e' | 'E') => python_exponent(&format!("{:.*e}", format.precision.unwrap_or(6), magnitude), upper),
+            Some('g' | 'G' | 'n') => general_format(magnitude, format.precision.unwrap_or(6), upper, format.alternate),
+            Some('%') => format!("{:.*}%", format.precision.unwrap_or(6), magnitude * 100.0),
+            Some(kind) => return Err(format!("Unknown format code '{}' for object of type 'float'", kind)),
+        };
+        // `z` only changes values that round to zero
+        if format.coerce_zero && !body.chars().any(|ch| ch.is_ascii_digit() && ch != '0') {
+            value = 0.0;
+        }
+        body
+    };
+    let body = match format.grouping {
+        Some(separator) => {
+            let integer_end = body.find(|ch: char| !ch.is_ascii_digit()).unwrap_or(body.len());
+            format!("{}{}", group_digits(&body[..integer_end], separator, 3), &body[integer_end..])
+        }
+        None => body,
+    };
+    let body = if format.alternate && !body.contains('.') && format.kind.is_some() && value.is_finite() {
+        // `#` always shows the decimal point
+        match body.find(['e', 'E', '%']) {
+            Some(index) => format!("{}.{}", &body[..index], &body[index..]),
+            None => format!("{}.", body),
+        }
+    } else {
+        body
+    };
+    let negative = value.is_sign_negative() && !value.is_nan();
+    Ok(pad(sign_prefix(negative, format), &body, format, '>'))
+}
+
+fn format_string(value: &str, format: &FormatSpec) -> Result<String, String> {
+    match format.kind {
+        None | Some('s') => {}
+        Some(kind) => return Err(format!("Unknown format code '{}' for object of type 'str'", kind)),
+    }
+    if format.sign != '-' {
+        return Err("Sign not allowed in string format specifier".to_string());
+    }
+    if format.align == Some('=') {
+        return Err("'=' alignment not allowed in string format specifier".to_string());
+    }
+    let text: String = match format.precision {
+        Some(precision) => value.chars().take(precision).collect(),
+        None => value.to_string(),
+    };
+    Ok(pad("", &text, format, '<'))
+}
+
+/// Format a value following the rules of Python `format(value, spec)`.
+pub fn format_value(value: &Value, spec: &str) -> Result<String, String> {
+    if spec.is_empty() {
+        return Ok(value.to_string());
+    }
+    let format = parse_spec(spec)?;
+    match value {
+        Value::Integer(integer) => format_integer(*integer, &format),
+        Value::Boolean(boolean) => format_integer(*boolean as i64, &format),
+        Value::Float(float) => format_float(*float, &format),
+        Value::String(text) => format_string(text, &format),
+        _ => Err(format!("unsupported format string passed to {}.__format__", value.type_name())),
+    }
+}
@@ -1,11 +1,57 @@
-use std::collections::HashMap;
-// #![allow(warnings)]
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::rc::Rc;
 
-// Define a struct for storing variables and their values
-#[derive(Clone,Debug)]
-pub struct Variable {
-    name: String,
-    value: Value,
+use crate::format::{float_repr, format_value};
+use crate::tokenizer::Span;
+
+// The storage of a variable, shared by its scope and the closures that use
+// it. It's empty while the name is not bound.
+type Cell = Rc<RefCell<Option<Value>>>;
+
+fn new_cell(value: Option<Value>) -> Cell {
+    Rc::new(RefCell::new(value))
+}
+
+// The variables of the module or of a function call. A call has a cell for
+// each of its local names from the start, like Python decides them when the
+// function is compiled.
+pub struct Scope {
+    variables: RefCell<HashMap<String, Cell>>,
+    // names declared `global` in the function
+    globals: HashSet<String>,
+    // the cells of the enclosing calls that the function uses, nonlocal
+    // names included
+    free: HashMap<String, Cell>,
+}
+
+impl Scope {
+    fn module() -> Self {
+        Scope { variables: RefCell::new(HashMap::new()), globals: HashSet::new(), free: HashMap::new() }
+    }
+
+    fn cell(&self, name: &str) -> Option<Cell> {
+        self.variables.borrow().get(name).cloned()
+    }
+}
+
+// only the names, the values can hold closures that refer back to the scope
+impl fmt::Debug for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut names: Vec<String> = self.variables.borrow().keys().cloned().collect();
+        names.sort();
+        f.debug_struct("Scope").field("names", &names).finish()
+    }
+}
+
+// Where a name is found by the LEGB rules, builtins are looked up after the globals
+enum Binding {
+    Local(Cell),
+    // a variable of an enclosing function
+    Free(Cell),
+    Global,
 }
 
 // Define a Value enum for storing different types of variable values
@@ -16,33 +62,411 @@ pub enum Value {
     Integer(i64),
     Float(f64),
     Boolean(bool),
+    // real and imaginary parts
+    Complex(f64, f64),
     String(String),
+    Bytes(Vec<u8>),
     // compund
     List(Vec<Value>),
     Tuple(Vec<Value>),
     Set(Vec<Value>),
-    // Dictionary(HashMap<String, Value>),
+    // keys and values in insertion order
+    Dict(Vec<(Value, Value)>),
+    // start, stop and step, the step is never 0
+    Range(i64, i64, i64),
+    Function(Rc<FunctionObject>),
+    Builtin(Builtin),
+    Iterator(Rc<IteratorObject>),
+    TypeAlias(Rc<TypeAliasObject>),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::None => "NoneType",
+            Value::Integer(_) => "int",
+            Value::Float(_) => "float",
+            Value::Boolean(_) => "bool",
+            Value::Complex(_, _) => "complex",
+            Value::String(_) => "str",
+            Value::Bytes(_) => "bytes",
+            Value::List(_) => "list",
+            Value::Tuple(_) => "tuple",
+            Value::Set(_) => "set",
+            Value::Dict(_) => "dict",
+            Value::Range(_, _, _) => "range",
+            Value::Function(_) => "function",
+            // `range` is a class, the other builtins are functions
+            Value::Builtin(Builtin::Range) => "type",
+            Value::Builtin(_) => "builtin_function_or_method",
+            Value::Iterator(iterator) => iterator.type_name,
+            Value::TypeAlias(_) => "TypeAliasType",
+        }
+    }
+
+    // Truth value like Python `bool()`, empty containers and zeros are false
+    pub fn truthy(&self) -> bool {
+        match self {
+            Value::None => false,
+            Value::Integer(integer) => *integer != 0,
+            Value::Float(float) => *float != 0.0,
+            Value::Boolean(boolean) => *boolean,
+            Value::Complex(real, imaginary) => *real != 0.0 || *imaginary != 0.0,
+            Value::String(text) => !text.is_empty(),
+            Value::Bytes(bytes) => !bytes.is_empty(),
+            Value::List(items) | Value::Tuple(items) | Value::Set(items) => !items.is_empty(),
+            Value::Dict(items) => !items.is_empty(),
+            Value::Range(start, stop, step) => range_len(*start, *stop, *step) > 0,
+            Value::Function(_) | Value::Builtin(_) | Value::Iterator(_) | Value::TypeAlias(_) => true,
+        }
+    }
+
+    // Representation of the value as Python `repr()` shows it
+    pub fn repr(&self) -> String {
+        match self {
+            Value::String(text) => string_repr(text),
+            Value::Bytes(bytes) => bytes_repr(bytes),
+            Value::List(items) => format!("[{}]", join_repr(items)),
+            Value::Tuple(items) if items.len() == 1 => format!("({},)", items[0].repr()),
+            Value::Tuple(items) => format!("({})", join_repr(items)),
+            Value::Set(items) if items.is_empty() => "set()".to_string(),
+            Value::Set(items) => format!("{{{}}}", join_repr(items)),
+            Value::Dict(items) => {
+                let items: Vec<String> = items.iter().map(|(key, value)| format!("{}: {}", key.repr(), value.repr())).collect();
+                format!("{{{}}}", items.join(", "))
+            }
+            Value::Range(start, stop, 1) => format!("range({}, {})", start, stop),
+            Value::Range(start, stop, step) => format!("range({}, {}, {})", start, stop, step),
+            Value::Function(function) => format!("<function {}>", function.function.name),
+            Value::Builtin(Builtin::Range) => "<class 'range'>".to_string(),
+            Value::Builtin(builtin) => format!("<built-in function {}>", builtin.name()),
+            Value::Iterator(iterator) => format!("<{} object>", iterator.type_name),
+            Value::TypeAlias(alias) => alias.name.clone(),
+            _ => self.to_string(),
+        }
+    }
+}
+
+fn join_repr(items: &[Value]) -> String {
+    items.iter().map(Value::repr).collect::<Vec<_>>().join(", ")
+}
+
+// strings prefer single quotes, unless only single quotes are inside of them
+fn string_repr(text: &str) -> String {
+    let quote = if text.contains('\'') && !text.contains('"') { '"' } else { '\'' };
+    let mut repr = String::from(quote);
+    for ch in text.chars() {
+        match ch {
+            '\\' => repr.push_str("\\\\"),
+            '\n' => repr.push_str("\\n"),
+            '\r' => repr.push_str("\\r"),
+            '\t' => repr.push_str("\\t"),
+            _ if ch == quote => {
+                repr.push('\\');
+                repr.push(ch);
+            }
+            _ if (ch as u32) < 0x20 || (0x7f..0xa0).contains(&(ch as u32)) => {
+                repr.push_str(&format!("\\x{:02x}", ch as u32));
+            }
+            _ => repr.push(ch),
+        }
+    }
+    repr.push(quote);
+    repr
+}
+
+fn bytes_repr(bytes: &[u8]) -> String {
+    let quote = if bytes.contains(&b'\'') && !bytes.contains(&b'"') { b'"' } else { b'\'' };
+    let mut repr = String::from("b");
+    repr.push(quote as char);
+    for &byte in bytes {
+        match byte {
+            b'\\' => repr.push_str("\\\\"),
+            b'\n' => repr.push_str("\\n"),
+            b'\r' => repr.push_str("\\r"),
+            b'\t' => repr.push_str("\\t"),
+            _ if byte == quote => {
+                repr.push('\\');
+                repr.push(byte as char);
+            }
+            0x20..=0x7e => repr.push(byte as char),
+            _ => repr.push_str(&format!("\\x{:02x}", byte)),
+        }
+    }
+    repr.push(quote as char);
+    repr
+}
+
+// the parts of a complex number drop the `.0` of integral values
+fn complex_part(value: f64) -> String {
+    let repr = float_repr(value);
+    repr.strip_suffix(".0").map(str::to_string).unwrap_or(repr)
+}
+
+// Text of the value as Python `str()` shows it
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::None => write!(f, "None"),
+            Value::Integer(integer) => write!(f, "{}", integer),
+            Value::Float(float) => write!(f, "{}", float_repr(*float)),
+            Value::Boolean(true) => write!(f, "True"),
+            Value::Boolean(false) => write!(f, "False"),
+            Value::Complex(real, imaginary) if *real == 0.0 && real.is_sign_positive() => {
+                write!(f, "{}j", complex_part(*imaginary))
+            }
+            Value::Complex(real, imaginary) => {
+                let sign = if imaginary.is_sign_negative() { "" } else { "+" };
+                write!(f, "({}{}{}j)", complex_part(*real), sign, complex_part(*imaginary))
+            }
+            Value::String(text) => write!(f, "{}", text),
+            _ => write!(f, "{}", self.repr()),
+        }
+    }
 }
 
 // Define a struct for storing functions and their parameters and code blocks
 #[derive(Clone,Debug)]
 pub struct Function {
     pub name: String,
-    pub params: Vec<(String, Option<Value>)>,
+    pub params: Vec<Parameter>,
     pub body: Vec<Statement>,
 }
 
+// How a parameter gets its argument, from its place around `/` and `*` and
+// its prefix, like in `def f(a, /, b, *args, c, **kwargs)`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ParameterKind {
+    PositionalOnly,
+    PositionalOrKeyword,
+    // `*args`, the extra positional arguments as a tuple
+    VarPositional,
+    KeywordOnly,
+    // `**kwargs`, the extra keyword arguments as a dict
+    VarKeyword,
+}
+
+#[derive(Clone, Debug)]
+pub struct Parameter {
+    pub name: String,
+    pub kind: ParameterKind,
+    // evaluated once, when the `def` runs
+    pub default: Option<Expression>,
+}
+
+// The value of a function, made when its `def` runs. The defaults are
+// evaluated once and copied into each call. Lists and dicts are values here,
+// so a call that changes its default doesn't affect the next one.
+pub struct FunctionObject {
+    pub function: Function,
+    pub defaults: Vec<Option<Value>>,
+    // the parameters and the names bound in the body, minus the ones
+    // declared `global` or `nonlocal`
+    pub locals: Vec<String>,
+    pub globals: HashSet<String>,
+    // the cells of its free variables, taken from the call it was defined in.
+    // Only these are kept, not the whole scope, so the other variables of
+    // the call don't live as long as the function.
+    pub free: HashMap<String, Cell>,
+}
+
+// the free variables by name only, a recursive closure holds itself
+impl fmt::Debug for FunctionObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut free: Vec<&String> = self.free.keys().collect();
+        free.sort();
+        f.debug_struct("FunctionObject")
+            .field("function", &self.function)
+            .field("defaults", &self.defaults)
+            .field("locals", &self.locals)
+            .field("globals", &self.globals)
+            .field("free", &free)
+            .finish()
+    }
+}
+
+// a function is only equal to itself
+impl PartialEq for FunctionObject {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PartialOrd for FunctionObject {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (self == other).then_some(Ordering::Equal)
+    }
+}
+
+// The value of `type Name[T] = value`. Like in Python the value is only
+// evaluated when `__value__` is read, by a function made where the alias is
+// defined, so it can refer to names defined later. There are no TypeVar
+// objects, the type parameters are passed to it as their names.
+#[derive(Debug, PartialEq, PartialOrd)]
+pub struct TypeAliasObject {
+    pub name: String,
+    pub parameters: Vec<String>,
+    pub value: Rc<FunctionObject>,
+}
+
+// Functions and types of the builtins scope, written in Rust
+#[derive(Clone, Copy, Debug, PartialEq, PartialOrd)]
+pub enum Builtin {
+    Range,
+    Iter,
+    Next,
+}
+
+impl Builtin {
+    fn from_name(name: &str) -> Option<Builtin> {
+        match name {
+            "range" => Some(Builtin::Range),
+            "iter" => Some(Builtin::Iter),
+            "next" => Some(Builtin::Next),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Builtin::Range => "range",
+            Builtin::Iter => "iter",
+            Builtin::Next => "next",
+        }
+    }
+}
+
+// Where an iterator is in the items it gives
+#[derive(Debug)]
+pub enum IteratorState {
+    // the items of a list, tuple, string, bytes, dict or set, copied when the
+    // iterator is made, and the position of the next one
+    Items(Vec<Value>, usize),
+    Range { next: i64, stop: i64, step: i64 },
+    // `iter(callable, sentinel)` calls a function or builtin until it returns the sentinel
+    Callable(Value, Value),
+    Exhausted,
+}
+
+// The value of `iter()`, shared so a `for` loop and `next()` advance the same one
+#[derive(Debug)]
+pub struct IteratorObject {
+    pub type_name: &'static str,
+    pub state: RefCell<IteratorState>,
+}
+
+// an iterator is only equal to itself
+impl PartialEq for IteratorObject {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+impl PartialOrd for IteratorObject {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        (self == other).then_some(Ordering::Equal)
+    }
+}
+
+// Where an assignment or a `for` loop stores a value
+#[derive(Clone, Debug)]
+pub enum Target {
+    Name(String),
+    // `object.name`
+    Attribute(Expression, String),
+    // `object[index]`, the object is changed where it's stored
+    Subscript(Expression, Expression),
+    // `a, b`, `(a, b)` or `[a, b]`, unpacking the items of the value
+    Sequence(Vec<Target>),
+    // `*rest` inside a sequence, the items left over as a list
+    Starred(Box<Target>),
+}
+
+// Arguments of a call, `f(a, *args, key=value, **kwargs)`
+#[derive(Clone, Debug)]
+pub enum Argument {
+    Positional(Expression),
+    Keyword(String, Expression),
+    // `*iterable`, its items are positional arguments
+    Unpack(Expression),
+    // `**mapping`, its items are keyword arguments
+    UnpackMapping(Expression),
+}
+
+/// A statement with the place of its first line in the source, where its
+/// runtime errors are reported.
+#[derive(Clone, Debug)]
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Option<Span>,
+}
+
+impl Statement {
+    pub fn new(kind: StatementKind, span: Span) -> Self {
+        Statement { kind, span: Some(span) }
+    }
+}
+
+// statements made by the interpreter, like the body of a lambda
+impl From<StatementKind> for Statement {
+    fn from(kind: StatementKind) -> Self {
+        Statement { kind, span: None }
+    }
+}
+
 // Define a Statement enum for storing different types of statements
 #[derive(Clone,Debug)]
-pub enum Statement {
-    Assignment(String, Expression),
+pub enum StatementKind {
+    // `a = b = value` has a target for each `=`
+    Assignment(Vec<Target>, Expression),
+    // `target += value` and the other operators
+    AugmentedAssignment(Target, BinaryOperator, Expression),
     Expression(Expression),
     If(Expression, Vec<Statement>, Option<Vec<Statement>>),
-    While(Expression, Vec<Statement>),
-    For(String, Expression, Expression, Vec<Statement>),
+    // the optional block is the `else:` of the loop
+    While(Expression, Vec<Statement>, Option<Vec<Statement>>),
+    For(Target, Expression, Vec<Statement>, Option<Vec<Statement>>),
     Function(Function),
     Return(Expression),
     Import(String),
+    Pass,
+    Break,
+    Continue,
+    Global(Vec<String>),
+    Nonlocal(Vec<String>),
+    // `match subject:` with its `case` blocks, tried in order
+    Match(Expression, Vec<MatchCase>),
+    // `type Name[T, U] = value`
+    TypeAlias(String, Vec<String>, Expression),
+    // placeholder for a statement that failed to parse
+    Error,
+}
+
+// `case pattern if guard:` and its block
+#[derive(Clone, Debug)]
+pub struct MatchCase {
+    pub pattern: Pattern,
+    pub guard: Option<Expression>,
+    pub body: Vec<Statement>,
+}
+
+// Patterns of the `case` blocks of a match statement
+#[derive(Clone, Debug)]
+pub enum Pattern {
+    // `_` matches anything without binding it
+    Wildcard,
+    Capture(String),
+    Literal(Value),
+    // dotted name like `Color.RED`, compared with `==`
+    Value(Expression),
+    // `[a, b]`, `(a, b)` or `a, b`, matching lists and tuples
+    Sequence(Vec<Pattern>),
+    // `*rest` inside a sequence, `*_` doesn't bind
+    Star(Option<String>),
+    Or(Vec<Pattern>),
+    As(Box<Pattern>, String),
+    // `{key: pattern, **rest}`, the keys are literal or value patterns
+    Mapping(Vec<(Pattern, Pattern)>, Option<String>),
 }
 
 #[derive(Clone, Debug)]
@@ -51,8 +475,27 @@ pub enum Expression {
     Binary(Box<Expression>, BinaryOperator, Box<Expression>),
     Unary(UnaryOperator, Box<Expression>),
     Literal(Value),
+    // integer literal too large for an i64, evaluating it raises OverflowError
+    LargeInteger(String),
     Variable(String),
-    FunctionCall(String, Vec<Expression>),
+    // the callee can be any expression, `obj.method(...)` or `fns[0](...)`
+    FunctionCall(Box<Expression>, Vec<Argument>),
+    Attribute(Box<Expression>, String),
+    Subscript(Box<Expression>, Box<Expression>),
+    // f-string made by literal strings and formatted values
+    JoinedStr(Vec<Expression>),
+    // value with an optional `!r`, `!s` or `!a` conversion and a format spec
+    FormattedValue(Box<Expression>, Option<char>, Option<Box<Expression>>),
+    // chain of comparisons like `a < b <= c`, each operand is evaluated once
+    Compare(Box<Expression>, Vec<(BinaryOperator, Expression)>),
+    // `body if test else orelse`, stored as test, body, orelse
+    Conditional(Box<Expression>, Box<Expression>, Box<Expression>),
+    Lambda(Vec<Parameter>, Box<Expression>),
+    Await(Box<Expression>),
+    Tuple(Vec<Expression>),
+    List(Vec<Expression>),
+    Set(Vec<Expression>),
+    Dict(Vec<(Expression, Expression)>),
 }
 
 // Define a BinaryOperator enum for storing different types of binary operators
@@ -62,251 +505,2005 @@ pub enum BinaryOperator {
     Subtract,
     Multiply,
     Divide,
+    FloorDivide,
     Modulo,
+    Power,
+    MatrixMultiply,
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    ShiftLeft,
+    ShiftRight,
     Equal,
     NotEqual,
     LessThan,
     GreaterThan,
     LessThanOrEqual,
     GreaterThanOrEqual,
+    In,
+    NotIn,
+    Is,
+    IsNot,
+    // logical, they short-circuit and give one of the operands
     And,
     Or,
 }
 
+impl BinaryOperator {
+    // operator as written in Python, used by the error messages
+    fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::FloorDivide => "//",
+            BinaryOperator::Modulo => "%",
+            BinaryOperator::Power => "** or pow()",
+            BinaryOperator::MatrixMultiply => "@",
+            BinaryOperator::BitwiseAnd => "&",
+            BinaryOperator::BitwiseOr => "|",
+            BinaryOperator::BitwiseXor => "^",
+            BinaryOperator::ShiftLeft => "<<",
+            BinaryOperator::ShiftRight => ">>",
+            BinaryOperator::Equal => "==",
+            BinaryOperator::NotEqual => "!=",
+            BinaryOperator::LessThan => "<",
+            BinaryOperator::GreaterThan => ">",
+            BinaryOperator::LessThanOrEqual => "<=",
+            BinaryOperator::GreaterThanOrEqual => ">=",
+            BinaryOperator::In => "in",
+            BinaryOperator::NotIn => "not in",
+            BinaryOperator::Is => "is",
+            BinaryOperator::IsNot => "is not",
+            BinaryOperator::And => "and",
+            BinaryOperator::Or => "or",
+        }
+    }
+}
+
 // Define a UnaryOperator enum for storing different types of unary operators
 #[derive(Clone,Debug)]
 pub enum UnaryOperator {
     Not,
     Minus,
+    Plus,
+    // bitwise not, `~`
+    Invert,
 }
 
-// Define a struct for storing the interpreter state
-#[derive(Clone,Debug)]
-struct Interpreter {
-    variables: Vec<Variable>,
-    functions: Vec<Function>,
+// functions and builtins, there are no classes or callable objects
+fn is_callable(value: &Value) -> bool {
+    matches!(value, Value::Function(_) | Value::Builtin(_))
 }
 
-// Implement the Interpreter struct
-impl Interpreter {
-    // Define a function to evaluate an expression and return its value
-    fn eval_expression(&self, expr: &Expression) -> Value {
-        match expr {
-            Expression::Binary(left, op, right) => {
-                let left_value = self.eval_expression(left);
-                let right_value = self.eval_expression(right);
-                match op {
-                    BinaryOperator::Add => match (left_value, right_value) {
-                        (Value::Integer(l), Value::Integer(r)) => Value::Integer(l + r),
-                        (Value::Float(l), Value::Float(r)) => Value::Float(l + r),
-                        (Value::String(l), Value::String(r)) => Value::String(format!("{}{}", l, r)),
-                        _ => panic!("Invalid operands for addition"),
-                    },
-                    BinaryOperator::Subtract => match (left_value, right_value) {
-                        (Value::Integer(l), Value::Integer(r)) => Value::Integer(l - r),
-                        (Value::Float(l), Value::Float(r)) => Value::Float(l - r),
-                        _ => panic!("Invalid operands for subtraction"),
-                    },
-                    BinaryOperator::Multiply => match (left_value, right_value) {
-                        (Value::Integer(l), Value::Integer(r)) => Value::Integer(l * r),
-                        (Value::Float(l), Value::Float(r)) => Value::Float(l * r),
-                        _ => panic!("Invalid operands for multiplication"),
-                    },
-                    BinaryOperator::Divide => match (left_value, right_value) {
-                        (Value::Integer(l), Value::Integer(r)) => Value::Integer(l / r),
-                        (Value::Float(l), Value::Float(r)) => Value::Float(l / r),
-                        _ => panic!("Invalid operands for division"),
-                    },
-                    BinaryOperator::Modulo => match (left_value, right_value) {
-                        (Value::Integer(l), Value::Integer(r)) => Value::Integer(l % r),
-                        _ => panic!("Invalid operands for modulo"),
-                    },
-                    BinaryOperator::Equal => Value::Boolean(left_value == right_value),
-                    BinaryOperator::NotEqual => Value::Boolean(left_value != right_value),
-                    BinaryOperator::LessThan => Value::Boolean(left_value < right_value),
-                    BinaryOperator::GreaterThan => Value::Boolean(left_value > right_value),
-                    BinaryOperator::LessThanOrEqual => Value::Boolean(left_value <= right_value),
-                    BinaryOperator::GreaterThanOrEqual => Value::Boolean(left_value >= right_value),
-                    BinaryOperator::And => match (left_value, right_value) {
-                        (Value::Integer(l), Value::Integer(r)) => Value::Integer(l & r),
-                        _ => panic!("Invalid operands for bitwise AND"),
-                    },
-                    BinaryOperator::Or => match (left_value, right_value) {
-                        (Value::Integer(l), Value::Integer(r)) => Value::Integer(l | r),
-                        _ => panic!("Invalid operands for bitwise OR"),
-                    },
-                    // Implement other binary operators as needed
-                    _ => unimplemented!(),
-                }
-            }
-            Expression::Unary(op, expr) => {
-                let value = self.eval_expression(expr);
-                match op {
-                    UnaryOperator::Not => match value {
-                        Value::Boolean(b) => Value::Boolean(!b),
-                        _ => panic!("Invalid operand for 'not' operator"),
-                    },
-                    UnaryOperator::Minus => match value {
-                        Value::Integer(i) => Value::Integer(-i),
-                        Value::Float(f) => Value::Float(-f),
-                        _ => panic!("Invalid operand for minus operator"),
-                    },
-                }
-            }
-            Expression::Literal(value) => value.clone(),
-            Expression::Variable(name) => {
-                let variable = self
-                    .variables
-                    .iter()
-                    .find(|v| v.name == *name)
-                    .expect("Variable not found");
-                variable.value.clone()
-            }
-            Expression::FunctionCall(name, args) => {
-                let function = self
-                    .functions
-                    .iter()
-                    .find(|f| f.name == *name)
-                    .expect("Function not declared");
-                let mut local_variables = Vec::new();
-                for (param, arg) in function.params.iter().zip(args.iter()) {
-                    dbg!(&param);
-                    dbg!(&arg);
-                    let (param, default) = param;
-                    dbg!(&default);
-                    local_variables.push(Variable {
-                        name: param.clone(),
-                        // value: if value { value } else {default.unwrap()},
-                        value: self.eval_expression(arg),
-                    });
-                }
-                let mut interpreter = Interpreter {
-                    variables: local_variables,
-                    functions: self.functions.clone(),
-                };
-                for statement in &function.body {
-                    interpreter.eval_statement(statement);
-                }
-                // Return value is not handled in this example
-                Value::None
+// booleans are integers in arithmetic, like in Python
+fn as_integer(value: &Value) -> Option<i64> {
+    match value {
+        Value::Integer(integer) => Some(*integer),
+        Value::Boolean(boolean) => Some(*boolean as i64),
+        _ => None,
+    }
+}
+
+fn as_float(value: &Value) -> Option<f64> {
+    match value {
+        Value::Float(float) => Some(*float),
+        _ => as_integer(value).map(|integer| integer as f64),
+    }
+}
+
+// ints and floats are complex numbers with no imaginary part
+fn as_complex(value: &Value) -> Option<(f64, f64)> {
+    match value {
+        Value::Complex(real, imaginary) => Some((*real, *imaginary)),
+        _ => as_float(value).map(|float| (float, 0.0)),
+    }
+}
+
+fn overflow() -> Exception {
+    Exception::new("OverflowError", "integer overflow")
+}
+
+// Python rounds the integer division down, so the remainder has the sign of the divisor
+fn floor_divide(left: i64, right: i64) -> Result<(i64, i64), Exception> {
+    if right == 0 {
+        return Err(Exception::new("ZeroDivisionError", "integer division or modulo by zero"));
+    }
+    let quotient = left.checked_div(right).ok_or_else(overflow)?;
+    let remainder = left % right;
+    if remainder != 0 && (remainder < 0) != (right < 0) {
+        Ok((quotient - 1, remainder + right))
+    } else {
+        Ok((quotient, remainder))
+    }
+}
+
+fn integer_operation(op: &BinaryOperator, left: i64, right: i64) -> Result<Option<Value>, Exception> {
+    let value = match op {
+        BinaryOperator::Add => left.checked_add(right).ok_or_else(overflow)?,
+        BinaryOperator::Subtract => left.checked_sub(right).ok_or_else(overflow)?,
+        BinaryOperator::Multiply => left.checked_mul(right).ok_or_else(overflow)?,
+        BinaryOperator::Divide => {
+            if right == 0 {
+                return Err(Exception::new("ZeroDivisionError", "division by zero"));
             }
+            return Ok(Some(Value::Float(left as f64 / right as f64)));
+        }
+        BinaryOperator::FloorDivide => floor_divide(left, right)?.0,
+        BinaryOperator::Modulo => floor_divide(left, right)?.1,
+        // a negative exponent gives a float
+        BinaryOperator::Power if right < 0 => return float_operation(op, left as f64, right as f64),
+        BinaryOperator::Power => u32::try_from(right)
+            .ok()
+            .and_then(|exponent| left.checked_pow(exponent))
+            .ok_or_else(overflow)?,
+        BinaryOperator::BitwiseAnd => left & right,
+        BinaryOperator::BitwiseOr => left | right,
+        BinaryOperator::BitwiseXor => left ^ right,
+        BinaryOperator::ShiftLeft | BinaryOperator::ShiftRight if right < 0 => {
+            return Err(Exception::new("ValueError", "negative shift count"));
+        }
+        BinaryOperator::ShiftLeft => match left {
+            0 => 0,
+            _ if right >= 63 => return Err(overflow()),
+            _ if (left << right) >> right != left => return Err(overflow()),
+            _ => left << right,
+        },
+        BinaryOperator::ShiftRight => left >> right.min(63),
+        _ => return Ok(None),
+    };
+    Ok(Some(Value::Integer(value)))
+}
+
+fn float_operation(op: &BinaryOperator, left: f64, right: f64) -> Result<Option<Value>, Exception> {
+    let zero_division = |message: &str| Err(Exception::new("ZeroDivisionError", message));
+    let value = match op {
+        BinaryOperator::Add => left + right,
+        BinaryOperator::Subtract => left - right,
+        BinaryOperator::Multiply => left * right,
+        BinaryOperator::Divide if right == 0.0 => return zero_division("float division by zero"),
+        BinaryOperator::Divide => left / right,
+        BinaryOperator::FloorDivide if right == 0.0 => return zero_division("float floor division by zero"),
+        BinaryOperator::FloorDivide => (left / right).floor(),
+        BinaryOperator::Modulo if right == 0.0 => return zero_division("float modulo"),
+        BinaryOperator::Modulo => left - right * (left / right).floor(),
+        // an infinite exponent is left to powf, `0.0 ** -inf` is inf
+        BinaryOperator::Power if left == 0.0 && right < 0.0 && right.is_finite() => {
+            return zero_division("0.0 cannot be raised to a negative power");
+        }
+        // a negative number to a fractional power is a complex number
+        BinaryOperator::Power if left < 0.0 && right.is_finite() && right.fract() != 0.0 => {
+            let (real, imaginary) = complex_power((left, 0.0), (right, 0.0))?;
+            return Ok(Some(Value::Complex(real, imaginary)));
         }
+        BinaryOperator::Power => left.powf(right),
+        _ => return Ok(None),
+    };
+    Ok(Some(Value::Float(value)))
+}
+
+// `//` and `%` are not defined for complex numbers, they are a TypeError
+fn complex_operation(op: &BinaryOperator, left: (f64, f64), right: (f64, f64)) -> Result<Option<Value>, Exception> {
+    let ((a, b), (c, d)) = (left, right);
+    let (real, imaginary) = match op {
+        BinaryOperator::Add => (a + c, b + d),
+        BinaryOperator::Subtract => (a - c, b - d),
+        BinaryOperator::Multiply => (a * c - b * d, a * d + b * c),
+        BinaryOperator::Divide => complex_divide(left, right)?,
+        BinaryOperator::Power => complex_power(left, right)?,
+        _ => return Ok(None),
+    };
+    Ok(Some(Value::Complex(real, imaginary)))
+}
+
+// Scaled by the larger part of the divisor like CPython, so the squares of
+// its parts don't overflow
+fn complex_divide((a, b): (f64, f64), (c, d): (f64, f64)) -> Result<(f64, f64), Exception> {
+    if c == 0.0 && d == 0.0 {
+        return Err(Exception::new("ZeroDivisionError", "complex division by zero"));
     }
+    if c.abs() >= d.abs() {
+        let ratio = d / c;
+        let denominator = c + d * ratio;
+        Ok(((a + b * ratio) / denominator, (b - a * ratio) / denominator))
+    } else {
+        let ratio = c / d;
+        let denominator = c * ratio + d;
+        Ok(((a * ratio + b) / denominator, (b * ratio - a) / denominator))
+    }
+}
 
-    // Define a function to evaluate a statement
-    fn eval_statement(&mut self, statement: &Statement) {
-        dbg!(&statement);
-        match statement {
-            Statement::Import(name) => {}
-            Statement::Assignment(name, expr) => {
-                let value = self.eval_expression(expr);
-                if let Some(variable) = self.variables.iter_mut().find(|v| v.name == *name) {
-                    variable.value = value;
-                }
-                else {
-                    self.variables.push(Variable { name: name.clone(), value });
-                }
-            }
-            Statement::Expression(expr) => {
-                let ret = self.eval_expression(expr);
-                dbg!(ret);
-            }
-            Statement::If(cond, if_block, else_block) => {
-                let cond_value = self.eval_expression(cond);
-                if let Value::Boolean(true) = cond_value {
-                    for statement in if_block {
-                        self.eval_statement(statement);
-                    }
-                } else if let Some(block) = else_block {
-                    for statement in block {
-                        self.eval_statement(statement);
-                    }
-                }
-            }
-            Statement::While(cond, block) => {
-                while let Value::Boolean(true) = self.eval_expression(cond) {
-                    for statement in block {
-                        self.eval_statement(statement);
-                    }
-                }
+// Small integer exponents multiply, so `(1+2j) ** 2` is exactly `-3+4j`,
+// the others go through the polar form
+fn complex_power(base: (f64, f64), exponent: (f64, f64)) -> Result<(f64, f64), Exception> {
+    let ((a, b), (c, d)) = (base, exponent);
+    if c == 0.0 && d == 0.0 {
+        return Ok((1.0, 0.0));
+    }
+    if a == 0.0 && b == 0.0 {
+        if d != 0.0 || c < 0.0 {
+            return Err(Exception::new("ZeroDivisionError", "0.0 to a negative or complex power"));
+        }
+        return Ok((0.0, 0.0));
+    }
+    if d == 0.0 && c.fract() == 0.0 && c.abs() <= 100.0 {
+        let mut result = (1.0, 0.0);
+        let mut square = base;
+        let mut n = c.abs() as u32;
+        while n > 0 {
+            if n & 1 == 1 {
+                result = (result.0 * square.0 - result.1 * square.1, result.0 * square.1 + result.1 * square.0);
             }
-            Statement::For(var, start, end, block) => {
-                let start_value = self.eval_expression(start);
-                let end_value = self.eval_expression(end);
-                if let (Value::Integer(start), Value::Integer(end)) = (start_value, end_value) {
-                    for i in start..=end {
-                        self.variables.push(Variable {
-                            name: var.clone(),
-                            value: Value::Integer(i),
-                        });
-                        for statement in block {
-                            self.eval_statement(statement);
-                        }
-                        self.variables.retain(|v| v.name != *var);
-                    }
-                } else {
-                    panic!("Invalid range values for 'for' loop");
+            square = (square.0 * square.0 - square.1 * square.1, 2.0 * square.0 * square.1);
+            n >>= 1;
+        }
+        return if c < 0.0 { complex_divide((1.0, 0.0), result) } else { Ok(result) };
+    }
+    let modulus = a.hypot(b);
+    let argument = b.atan2(a);
+    let mut length = modulus.powf(c);
+    let mut phase = argument * c;
+    if d != 0.0 {
+        length /= (argument * d).exp();
+        phase += d * modulus.ln();
+    }
+    Ok((length * phase.cos(), length * phase.sin()))
+}
+
+// Longest sequence a repetition makes, a longer one raises MemoryError
+// instead of aborting the interpreter when the allocation fails
+const MAX_REPEAT_LEN: usize = 1 << 28;
+
+// sequences repeated by an integer, a negative count gives an empty sequence
+fn repeat(value: &Value, count: i64) -> Result<Option<Value>, Exception> {
+    let count = count.max(0) as usize;
+    let (len, too_long) = match value {
+        Value::String(text) => (text.len(), Exception::new("OverflowError", "repeated string is too long")),
+        Value::Bytes(bytes) => (bytes.len(), Exception::new("OverflowError", "repeated bytes are too long")),
+        Value::List(items) | Value::Tuple(items) => (items.len(), Exception::new("MemoryError", "")),
+        _ => return Ok(None),
+    };
+    // like in CPython, a size that doesn't fit in an isize is an overflow
+    match len.checked_mul(count) {
+        Some(total) if total > isize::MAX as usize => return Err(too_long),
+        None => return Err(too_long),
+        Some(total) if total > MAX_REPEAT_LEN => return Err(Exception::new("MemoryError", "")),
+        Some(_) => {}
+    }
+    Ok(Some(match value {
+        Value::String(text) => Value::String(text.repeat(count)),
+        Value::Bytes(bytes) => Value::Bytes(bytes.repeat(count)),
+        Value::List(items) => Value::List(items.iter().cloned().cycle().take(items.len() * count).collect()),
+        Value::Tuple(items) => Value::Tuple(items.iter().cloned().cycle().take(items.len() * count).collect()),
+        _ => unreachable!("only sequences have a length to repeat"),
+    }))
+}
+
+// Arithmetic and bitwise operators, comparisons are in `compare`.
+fn binary_operation(op: &BinaryOperator, left: Value, right: Value) -> Result<Value, Exception> {
+    let result = match (&left, &right) {
+        // `&`, `|` and `^` between booleans give a boolean
+        (Value::Boolean(l), Value::Boolean(r)) if matches!(op, BinaryOperator::BitwiseAnd | BinaryOperator::BitwiseOr | BinaryOperator::BitwiseXor) => {
+            Some(Value::Boolean(match op {
+                BinaryOperator::BitwiseAnd => l & r,
+                BinaryOperator::BitwiseOr => l | r,
+                _ => l ^ r,
+            }))
+        }
+        (Value::Complex(..), _) | (_, Value::Complex(..)) => match (as_complex(&left), as_complex(&right)) {
+            (Some(l), Some(r)) => complex_operation(op, l, r)?,
+            _ => None,
+        },
+        (Value::Float(_), _) | (_, Value::Float(_)) => match (as_float(&left), as_float(&right)) {
+            (Some(l), Some(r)) => float_operation(op, l, r)?,
+            _ => None,
+        },
+        _ => match (as_integer(&left), as_integer(&right)) {
+            (Some(l), Some(r)) => integer_operation(op, l, r)?,
+            _ => None,
+        },
+    };
+    let result = match result {
+        Some(result) => Some(result),
+        None => match (op, &left, &right) {
+            (BinaryOperator::Add, Value::String(l), Value::String(r)) => Some(Value::String(format!("{}{}", l, r))),
+            (BinaryOperator::Add, Value::Bytes(l), Value::Bytes(r)) => Some(Value::Bytes([l.as_slice(), r].concat())),
+            (BinaryOperator::Add, Value::List(l), Value::List(r)) => Some(Value::List([l.as_slice(), r].concat())),
+            (BinaryOperator::Add, Value::Tuple(l), Value::Tuple(r)) => Some(Value::Tuple([l.as_slice(), r].concat())),
+            (BinaryOperator::Multiply, sequence, count) if as_integer(count).is_some() => repeat(sequence, as_integer(count).unwrap())?,
+            (BinaryOperator::Multiply, count, sequence) if as_integer(count).is_some() => repeat(sequence, as_integer(count).unwrap())?,
+            _ => None,
+        },
+    };
+    result.ok_or_else(|| {
+        Exception::new(
+            "TypeError",
+            format!("unsupported operand type(s) for {}: '{}' and '{}'", op.symbol(), left.type_name(), right.type_name()),
+        )
+    })
+}
+
+// `==` between values, numbers of different types compare by their value
+fn values_equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::List(l), Value::List(r)) | (Value::Tuple(l), Value::Tuple(r)) => {
+            l.len() == r.len() && l.iter().zip(r).all(|(l, r)| values_equal(l, r))
+        }
+        (Value::Set(l), Value::Set(r)) => {
+            l.len() == r.len() && l.iter().all(|item| r.iter().any(|other| values_equal(item, other)))
+        }
+        // the order of the items doesn't matter
+        (Value::Dict(l), Value::Dict(r)) => {
+            l.len() == r.len() && l.iter().all(|(key, value)| matches!(dict_get(r, key), Some(other) if values_equal(value, other)))
+        }
+        (Value::Complex(..), _) | (_, Value::Complex(..)) => matches!((as_complex(left), as_complex(right)), (Some(l), Some(r)) if l == r),
+        _ => match (as_integer(left), as_integer(right)) {
+            (Some(l), Some(r)) => l == r,
+            _ => match (as_float(left), as_float(right)) {
+                (Some(l), Some(r)) => l == r,
+                _ => left == right,
+            },
+        },
+    }
+}
+
+// ordering used by `<`, `<=`, `>` and `>=`, None when the types can't be ordered
+fn order(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+        (Value::Bytes(l), Value::Bytes(r)) => Some(l.cmp(r)),
+        // sequences compare item by item, then by length
+        (Value::List(l), Value::List(r)) | (Value::Tuple(l), Value::Tuple(r)) => {
+            for (l, r) in l.iter().zip(r) {
+                if !values_equal(l, r) {
+                    return order(l, r);
                 }
             }
-            Statement::Function(function) => {
-                self.functions.push(function.clone());
-            }
-            Statement::Return(expr) => {
-                let _ = self.eval_expression(expr);
-                // Handling return value is not shown in this example
-            }
+            Some(l.len().cmp(&r.len()))
         }
+        _ => match (as_integer(left), as_integer(right)) {
+            (Some(l), Some(r)) => Some(l.cmp(&r)),
+            _ => as_float(left)?.partial_cmp(&as_float(right)?),
+        },
     }
 }
 
+fn contains(container: &Value, item: &Value) -> Result<bool, Exception> {
+    Ok(match (container, item) {
+        (Value::List(items) | Value::Tuple(items) | Value::Set(items), item) => items.iter().any(|other| values_equal(other, item)),
+        (Value::String(text), Value::String(part)) => text.contains(part.as_str()),
+        (Value::String(_), item) => {
+            let message = format!("'in <string>' requires string as left operand, not {}", item.type_name());
+            return Err(Exception::new("TypeError", message));
+        }
+        (Value::Bytes(bytes), Value::Bytes(part)) => part.is_empty() || bytes.windows(part.len()).any(|window| window == part.as_slice()),
+        (Value::Bytes(bytes), Value::Integer(byte)) => bytes.iter().any(|other| *other as i64 == *byte),
+        (Value::Dict(items), key) => dict_get(items, key).is_some(),
+        (Value::Range(start, stop, step), item) => match as_integer(item) {
+            Some(item) => {
+                let offset = item as i128 - *start as i128;
+                offset % *step as i128 == 0 && (0..range_len(*start, *stop, *step) as i128).contains(&(offset / *step as i128))
+            }
+            None => false,
+        },
+        _ => {
+            let message = format!("argument of type '{}' is not iterable", container.type_name());
+            return Err(Exception::new("TypeError", message));
+        }
+    })
+}
+
+fn dict_get<'a>(items: &'a [(Value, Value)], key: &Value) -> Option<&'a Value> {
+    items.iter().find(|(other, _)| values_equal(other, key)).map(|(_, value)| value)
+}
 
-pub fn main() {
-    // Create an instance of the interpreter
-    let mut interpreter = Interpreter {
-        variables: Vec::new(),
-        functions: Vec::new(),
+// number of integers in a range
+fn range_len(start: i64, stop: i64, step: i64) -> i64 {
+    let (start, stop, step) = (start as i128, stop as i128, step as i128);
+    let len = if step > 0 { (stop - start + step - 1) / step } else { (start - stop - step - 1) / -step };
+    len.max(0) as i64
+}
+
+// Iterator over the items of a value like Python `iter()`, None when the value
+// can't be iterated. An iterator gives itself.
+fn make_iterator(value: &Value) -> Option<Rc<IteratorObject>> {
+    let (type_name, state) = match value {
+        Value::Iterator(iterator) => return Some(Rc::clone(iterator)),
+        Value::Range(start, stop, step) => ("range_iterator", IteratorState::Range { next: *start, stop: *stop, step: *step }),
+        Value::List(items) => ("list_iterator", IteratorState::Items(items.clone(), 0)),
+        Value::Tuple(items) => ("tuple_iterator", IteratorState::Items(items.clone(), 0)),
+        Value::Set(items) => ("set_iterator", IteratorState::Items(items.clone(), 0)),
+        Value::Dict(items) => ("dict_keyiterator", IteratorState::Items(items.iter().map(|(key, _)| key.clone()).collect(), 0)),
+        Value::String(text) => {
+            let chars = text.chars().map(|ch| Value::String(ch.to_string())).collect();
+            ("str_iterator", IteratorState::Items(chars, 0))
+        }
+        Value::Bytes(bytes) => {
+            let bytes = bytes.iter().map(|byte| Value::Integer(*byte as i64)).collect();
+            ("bytes_iterator", IteratorState::Items(bytes, 0))
+        }
+        _ => return None,
     };
+    Some(Rc::new(IteratorObject { type_name, state: RefCell::new(state) }))
+}
 
-    // dbg!(Expression::Literal(Value::Integer(10)));
-    
-    let function = Function {
-        name: String::from("add"),
-        params: vec![(String::from("a"), None), (String::from("b"),None)],
-        body: vec![
-            Statement::Expression(Expression::Variable(String::from("a"))),
-            Statement::Expression(Expression::Variable(String::from("b"))),
-            Statement::Return(Expression::Literal(Value::None))
-        ],
+// Python index of a sequence, negative ones count from the end
+fn sequence_index(len: usize, index: &Value, type_name: &str) -> Result<usize, Exception> {
+    let Some(index) = as_integer(index) else {
+        let message = format!("{} indices must be integers, not {}", type_name, index.type_name());
+        return Err(Exception::new("TypeError", message));
     };
+    let position = if index < 0 { index + len as i64 } else { index };
+    if position < 0 || position >= len as i64 {
+        return Err(Exception::new("IndexError", format!("{} index out of range", type_name)));
+    }
+    Ok(position as usize)
+}
 
-    // Example code
-    let statements = vec![
-        Statement::Function(function),
-        // Statement::If(
-        //     Expression::Binary(
-        //         Box::new(Expression::Literal(Value::Integer(10))),
-        //         BinaryOperator::NotEqual,
-        //         Box::new(Expression::Literal(Value::Integer(20))),
-        //     ),
-        //     vec![Statement::Assignment(String::from("x"),Expression::Literal(Value::Float(3.6)))],
-        //     Some(vec![Statement::Assignment(String::from("x"),Expression::Literal(Value::Float(33.6)))]),
-        // ),
-        // Statement::Assignment(
-        //     String::from("u"),
-        //     Expression::Binary(
-        //         Box::new(Expression::Literal(Value::Integer(17))),
-        //         BinaryOperator::NotEqual,
-        //         Box::new(Expression::Literal(Value::Integer(2))),
-        //     ),
-        // ),
-        // Statement::Expression(Expression::FunctionCall("add".to_string(),vec![Expression::Literal(Value::Float(4.6)),Expression::Literal(Value::Float(43.6))])),
-        Statement::Expression(Expression::Literal(Value::Float(43.6))),
-        // Statement::Expression(Expression::Literal(Value::String("x".to_string()))),
-    ];
+fn subscript(value: &Value, index: &Value) -> Result<Value, Exception> {
+    Ok(match value {
+        Value::List(items) | Value::Tuple(items) => items[sequence_index(items.len(), index, value.type_name())?].clone(),
+        Value::String(text) => {
+            let chars: Vec<char> = text.chars().collect();
+            Value::String(chars[sequence_index(chars.len(), index, "string")?].to_string())
+        }
+        Value::Bytes(bytes) => Value::Integer(bytes[sequence_index(bytes.len(), index, "index")?] as i64),
+        Value::Dict(items) => match dict_get(items, index) {
+            Some(value) => value.clone(),
+            None => return Err(Exception::new("KeyError", index.repr())),
+        },
+        _ => return Err(Exception::new("TypeError", format!("'{}' object is not subscriptable", value.type_name()))),
+    })
+}
 
-    // Evaluate the statements
-    for statement in statements {
-        let s = interpreter.eval_statement(&statement);
-        dbg!(s);
+// there are no objects with attributes yet
+fn no_attribute(value: &Value, name: &str) -> Exception {
+    Exception::new("AttributeError", format!("'{}' object has no attribute '{}'", value.type_name(), name))
+}
+
+// The item of nested lists, dicts and tuples that the keys lead to, like
+// `value[0]["k"]`. A tuple can't be changed, but the list in it can.
+fn item_mut<'a>(value: &'a mut Value, keys: &[Value]) -> Result<&'a mut Value, Exception> {
+    let Some((key, rest)) = keys.split_first() else {
+        return Ok(value);
+    };
+    let type_name = value.type_name();
+    let item = match value {
+        Value::List(items) | Value::Tuple(items) => {
+            let position = sequence_index(items.len(), key, type_name)?;
+            &mut items[position]
+        }
+        Value::Dict(items) => match items.iter_mut().find(|(other, _)| values_equal(other, key)) {
+            Some((_, item)) => item,
+            None => return Err(Exception::new("KeyError", key.repr())),
+        },
+        _ => return Err(Exception::new("TypeError", format!("'{}' object is not subscriptable", type_name))),
+    };
+    item_mut(item, rest)
+}
+
+// `container[key] = value`, a dict gets a new key when it doesn't have it
+fn set_item(container: &mut Value, key: &Value, value: Value) -> Result<(), Exception> {
+    match container {
+        Value::List(items) => {
+            let position = sequence_index(items.len(), key, "list").map_err(|error| match error.kind.as_str() {
+                "IndexError" => Exception::new("IndexError", "list assignment index out of range"),
+                _ => error,
+            })?;
+            items[position] = value;
+        }
+        Value::Dict(items) => match items.iter_mut().find(|(other, _)| values_equal(other, key)) {
+            Some((_, item)) => *item = value,
+            None => items.push((key.clone(), value)),
+        },
+        _ => {
+            let message = format!("'{}' object does not support item assignment", container.type_name());
+            return Err(Exception::new("TypeError", message));
+        }
+    }
+    Ok(())
+}
+
+// "'a'", "'a' and 'b'" or "'a', 'b', and 'c'", like the messages of Python
+fn name_list(names: &[&str]) -> String {
+    let names: Vec<String> = names.iter().map(|name| format!("'{}'", name)).collect();
+    match names.len() {
+        1 => names[0].clone(),
+        2 => format!("{} and {}", names[0], names[1]),
+        len => format!("{}, and {}", names[..len - 1].join(", "), names[len - 1]),
+    }
+}
+
+fn plural(count: usize) -> &'static str {
+    if count == 1 { "" } else { "s" }
+}
+
+// values of the positional and of the keyword arguments of a call
+type Arguments = (Vec<Value>, Vec<(String, Value)>);
+
+// Local variables of a call. Positional arguments go to the parameters in
+// order and the extra ones to `*args`, keywords go by name or to `**kwargs`,
+// and defaults fill the rest. Mistakes are the TypeError of Python.
+fn bind_arguments(object: &FunctionObject, positional: Vec<Value>, keywords: Vec<(String, Value)>) -> Result<HashMap<String, Cell>, Exception> {
+    let name = &object.function.name;
+    let type_error = |message: String| Err(Exception::new("TypeError", message));
+    let params = &object.function.params;
+    let var_positional = params.iter().position(|param| param.kind == ParameterKind::VarPositional);
+    let var_keyword = params.iter().position(|param| param.kind == ParameterKind::VarKeyword);
+    let positional_slots: Vec<usize> = (0..params.len())
+        .filter(|&slot| matches!(params[slot].kind, ParameterKind::PositionalOnly | ParameterKind::PositionalOrKeyword))
+        .collect();
+
+    let mut values: Vec<Option<Value>> = vec![None; params.len()];
+    let given = positional.len();
+    let mut extra = Vec::new();
+    for (index, value) in positional.into_iter().enumerate() {
+        match positional_slots.get(index) {
+            Some(&slot) => values[slot] = Some(value),
+            None => extra.push(value),
+        }
+    }
+    if !extra.is_empty() && var_positional.is_none() {
+        let len = positional_slots.len();
+        let required = positional_slots.iter().filter(|&&slot| object.defaults[slot].is_none()).count();
+        let takes = match required == len {
+            true => format!("{} positional argument{}", len, plural(len)),
+            false => format!("from {} to {} positional arguments", required, len),
+        };
+        return type_error(format!("{}() takes {} but {} {} given", name, takes, given, if given == 1 { "was" } else { "were" }));
+    }
+
+    let mut extra_keywords = Vec::new();
+    let mut positional_only = Vec::new();
+    for (keyword, value) in keywords {
+        let slot = params.iter().position(|param| {
+            param.name == keyword && matches!(param.kind, ParameterKind::PositionalOrKeyword | ParameterKind::KeywordOnly)
+        });
+        match slot {
+            Some(slot) if values[slot].is_some() => {
+                return type_error(format!("{}() got multiple values for argument '{}'", name, keyword));
+            }
+            Some(slot) => values[slot] = Some(value),
+            None if var_keyword.is_some() => extra_keywords.push((Value::String(keyword), value)),
+            None if params.iter().any(|param| param.name == keyword) => positional_only.push(keyword),
+            None => return type_error(format!("{}() got an unexpected keyword argument '{}'", name, keyword)),
+        }
+    }
+    if !positional_only.is_empty() {
+        return type_error(format!(
+            "{}() got some positional-only arguments passed as keyword arguments: '{}'",
+            name, positional_only.join(", ")
+        ));
+    }
+
+    if let Some(slot) = var_positional {
+        values[slot] = Some(Value::Tuple(extra));
+    }
+    if let Some(slot) = var_keyword {
+        values[slot] = Some(Value::Dict(extra_keywords));
+    }
+    for (value, default) in values.iter_mut().zip(&object.defaults) {
+        if value.is_none() {
+            *value = default.clone();
+        }
+    }
+    for (kinds, description) in [
+        (&[ParameterKind::PositionalOnly, ParameterKind::PositionalOrKeyword][..], "positional"),
+        (&[ParameterKind::KeywordOnly][..], "keyword-only"),
+    ] {
+        let missing: Vec<&str> = params
+            .iter()
+            .zip(&values)
+            .filter(|(param, value)| value.is_none() && kinds.contains(&param.kind))
+            .map(|(param, _)| param.name.as_str())
+            .collect();
+        if !missing.is_empty() {
+            return type_error(format!(
+                "{}() missing {} required {} argument{}: {}",
+                name, missing.len(), description, plural(missing.len()), name_list(&missing)
+            ));
+        }
+    }
+    Ok(params
+        .iter()
+        .zip(values)
+        .map(|(param, value)| (param.name.clone(), new_cell(value)))
+        .collect())
+}
+
+fn compare(op: &BinaryOperator, left: &Value, right: &Value) -> Result<bool, Exception> {
+    let ordering = |accept: fn(Ordering) -> bool| match order(left, right) {
+        Some(ordering) => Ok(accept(ordering)),
+        // NaN is never ordered
+        None if as_float(left).is_some() && as_float(right).is_some() => Ok(false),
+        None => Err(Exception::new(
+            "TypeError",
+            format!("'{}' not supported between instances of '{}' and '{}'", op.symbol(), left.type_name(), right.type_name()),
+        )),
+    };
+    Ok(match op {
+        BinaryOperator::Equal => values_equal(left, right),
+        BinaryOperator::NotEqual => !values_equal(left, right),
+        BinaryOperator::LessThan => ordering(Ordering::is_lt)?,
+        BinaryOperator::GreaterThan => ordering(Ordering::is_gt)?,
+        BinaryOperator::LessThanOrEqual => ordering(Ordering::is_le)?,
+        BinaryOperator::GreaterThanOrEqual => ordering(Ordering::is_ge)?,
+        BinaryOperator::In => contains(right, left)?,
+        BinaryOperator::NotIn => !contains(right, left)?,
+        // values are copied, so identity is only known for the singletons and functions
+        BinaryOperator::Is => match (left, right) {
+            (Value::None, Value::None) => true,
+            (Value::Boolean(l), Value::Boolean(r)) => l == r,
+            (Value::Function(l), Value::Function(r)) => Rc::ptr_eq(l, r),
+            (Value::Iterator(l), Value::Iterator(r)) => Rc::ptr_eq(l, r),
+            (Value::TypeAlias(l), Value::TypeAlias(r)) => Rc::ptr_eq(l, r),
+            (Value::Builtin(l), Value::Builtin(r)) => l == r,
+            _ => false,
+        },
+        BinaryOperator::IsNot => !compare(&BinaryOperator::Is, left, right)?,
+        _ => unreachable!("{} is not a comparison", op.symbol()),
+    })
+}
+
+// escape the non-ASCII chars of a representation, like Python `ascii()`
+fn ascii(repr: &str) -> String {
+    repr.chars()
+        .map(|ch| match ch as u32 {
+            0..=0x7f => ch.to_string(),
+            0x80..=0xff => format!("\\x{:02x}", ch as u32),
+            0x100..=0xffff => format!("\\u{:04x}", ch as u32),
+            code => format!("\\U{:08x}", code),
+        })
+        .collect()
+}
+
+fn add_name(names: &mut Vec<String>, name: &str) {
+    if !names.iter().any(|other| other == name) {
+        names.push(name.to_string());
+    }
+}
+
+fn target_names(target: &Target, names: &mut Vec<String>) {
+    match target {
+        Target::Name(name) => add_name(names, name),
+        Target::Sequence(targets) => targets.iter().for_each(|target| target_names(target, names)),
+        Target::Starred(target) => target_names(target, names),
+        Target::Attribute(..) | Target::Subscript(..) => {}
+    }
+}
+
+pub fn pattern_names(pattern: &Pattern, names: &mut Vec<String>) {
+    match pattern {
+        Pattern::Capture(name) | Pattern::Star(Some(name)) => add_name(names, name),
+        Pattern::As(pattern, name) => {
+            pattern_names(pattern, names);
+            add_name(names, name);
+        }
+        Pattern::Sequence(patterns) | Pattern::Or(patterns) => patterns.iter().for_each(|pattern| pattern_names(pattern, names)),
+        Pattern::Mapping(items, rest) => {
+            items.iter().for_each(|(_, pattern)| pattern_names(pattern, names));
+            if let Some(name) = rest {
+                add_name(names, name);
+            }
+        }
+        Pattern::Wildcard | Pattern::Literal(_) | Pattern::Value(_) | Pattern::Star(None) => {}
+    }
+}
+
+// Names that the statements of a function body bind, and the ones they
+// declare `global` and `nonlocal`. A nested function only binds its name,
+// its body has its own scope.
+fn scan_names(statements: &[Statement], bound: &mut Vec<String>, globals: &mut HashSet<String>, nonlocals: &mut HashSet<String>) {
+    for statement in statements {
+        match &statement.kind {
+            StatementKind::Assignment(targets, _) => targets.iter().for_each(|target| target_names(target, bound)),
+            StatementKind::AugmentedAssignment(target, _, _) => target_names(target, bound),
+            StatementKind::Function(function) => add_name(bound, &function.name),
+            StatementKind::TypeAlias(name, _, _) => add_name(bound, name),
+            StatementKind::For(target, _, body, orelse) => {
+                target_names(target, bound);
+                scan_names(body, bound, globals, nonlocals);
+                scan_names(orelse.as_deref().unwrap_or_default(), bound, globals, nonlocals);
+            }
+            StatementKind::If(_, body, orelse) | StatementKind::While(_, body, orelse) => {
+                scan_names(body, bound, globals, nonlocals);
+                scan_names(orelse.as_deref().unwrap_or_default(), bound, globals, nonlocals);
+            }
+            StatementKind::Match(_, cases) => {
+                for case in cases {
+                    pattern_names(&case.pattern, bound);
+                    scan_names(&case.body, bound, globals, nonlocals);
+                }
+            }
+            StatementKind::Global(names) => globals.extend(names.iter().cloned()),
+            StatementKind::Nonlocal(names) => nonlocals.extend(names.iter().cloned()),
+            _ => {}
+        }
+    }
+}
+
+// The parameters and the names bound in the body of a function, with the
+// names it declares `global` and `nonlocal`
+fn local_names(function: &Function) -> (Vec<String>, HashSet<String>, HashSet<String>) {
+    let mut locals: Vec<String> = function.params.iter().map(|param| param.name.clone()).collect();
+    let (mut globals, mut nonlocals) = (HashSet::new(), HashSet::new());
+    scan_names(&function.body, &mut locals, &mut globals, &mut nonlocals);
+    (locals, globals, nonlocals)
+}
+
+// Names a function needs from the enclosing calls: the ones it reads that
+// aren't its own, and the free names of the functions nested in it, which
+// pass through it.
+fn free_names(function: &Function) -> HashSet<String> {
+    let (locals, globals, nonlocals) = local_names(function);
+    let mut names = HashSet::new();
+    statement_uses(&function.body, &mut names);
+    names.retain(|name| !locals.contains(name) && !globals.contains(name));
+    names.extend(nonlocals);
+    names
+}
+
+fn statement_uses(statements: &[Statement], names: &mut HashSet<String>) {
+    for statement in statements {
+        match &statement.kind {
+            StatementKind::Expression(expr) | StatementKind::Return(expr) => expression_uses(expr, names),
+            StatementKind::Assignment(targets, expr) => {
+                targets.iter().for_each(|target| target_uses(target, names));
+                expression_uses(expr, names);
+            }
+            StatementKind::AugmentedAssignment(target, _, expr) => {
+                target_uses(target, names);
+                expression_uses(expr, names);
+            }
+            StatementKind::If(test, body, orelse) | StatementKind::While(test, body, orelse) => {
+                expression_uses(test, names);
+                statement_uses(body, names);
+                statement_uses(orelse.as_deref().unwrap_or_default(), names);
+            }
+            StatementKind::For(target, iterable, body, orelse) => {
+                target_uses(target, names);
+                expression_uses(iterable, names);
+                statement_uses(body, names);
+                statement_uses(orelse.as_deref().unwrap_or_default(), names);
+            }
+            StatementKind::Function(function) => {
+                function.params.iter().filter_map(|param| param.default.as_ref()).for_each(|default| expression_uses(default, names));
+                names.extend(free_names(function));
+            }
+            StatementKind::Match(subject, cases) => {
+                expression_uses(subject, names);
+                for case in cases {
+                    pattern_uses(&case.pattern, names);
+                    if let Some(guard) = &case.guard {
+                        expression_uses(guard, names);
+                    }
+                    statement_uses(&case.body, names);
+                }
+            }
+            // the value of a type alias is evaluated like the body of a lambda
+            // with the type parameters as its parameters
+            StatementKind::TypeAlias(_, parameters, value) => {
+                let mut value_names = HashSet::new();
+                expression_uses(value, &mut value_names);
+                names.extend(value_names.into_iter().filter(|name| !parameters.contains(name)));
+            }
+            StatementKind::Import(_)
+            | StatementKind::Pass
+            | StatementKind::Break
+            | StatementKind::Continue
+            | StatementKind::Global(_)
+            | StatementKind::Nonlocal(_)
+            | StatementKind::Error => {}
+        }
+    }
+}
+
+fn expression_uses(expr: &Expression, names: &mut HashSet<String>) {
+    match expr {
+        Expression::Variable(name) => {
+            names.insert(name.clone());
+        }
+        Expression::Literal(_) | Expression::LargeInteger(_) => {}
+        Expression::Binary(left, _, right) | Expression::Subscript(left, right) => {
+            expression_uses(left, names);
+            expression_uses(right, names);
+        }
+        Expression::Unary(_, expr) | Expression::Attribute(expr, _) | Expression::Await(expr) => expression_uses(expr, names),
+        Expression::FunctionCall(callee, args) => {
+            expression_uses(callee, names);
+            for arg in args {
+                match arg {
+                    Argument::Positional(expr) | Argument::Keyword(_, expr) | Argument::Unpack(expr) | Argument::UnpackMapping(expr) => {
+                        expression_uses(expr, names)
+                    }
+                }
+            }
+        }
+        Expression::JoinedStr(items) | Expression::Tuple(items) | Expression::List(items) | Expression::Set(items) => {
+            items.iter().for_each(|item| expression_uses(item, names));
+        }
+        Expression::FormattedValue(expr, _, format_spec) => {
+            expression_uses(expr, names);
+            if let Some(format_spec) = format_spec {
+                expression_uses(format_spec, names);
+            }
+        }
+        Expression::Compare(first, rest) => {
+            expression_uses(first, names);
+            rest.iter().for_each(|(_, expr)| expression_uses(expr, names));
+        }
+        Expression::Conditional(test, body, orelse) => {
+            expression_uses(test, names);
+            expression_uses(body, names);
+            expression_uses(orelse, names);
+        }
+        // the parameters of a lambda are its own names
+        Expression::Lambda(params, body) => {
+            params.iter().filter_map(|param| param.default.as_ref()).for_each(|default| expression_uses(default, names));
+            let mut body_names = HashSet::new();
+            expression_uses(body, &mut body_names);
+            names.extend(body_names.into_iter().filter(|name| !params.iter().any(|param| param.name == *name)));
+        }
+        Expression::Dict(items) => {
+            for (key, value) in items {
+                expression_uses(key, names);
+                expression_uses(value, names);
+            }
+        }
+    }
+}
+
+// a name that an assignment binds is also used, `x += 1` reads `x`
+fn target_uses(target: &Target, names: &mut HashSet<String>) {
+    match target {
+        Target::Name(name) => {
+            names.insert(name.clone());
+        }
+        Target::Attribute(object, _) => expression_uses(object, names),
+        Target::Subscript(object, index) => {
+            expression_uses(object, names);
+            expression_uses(index, names);
+        }
+        Target::Sequence(targets) => targets.iter().for_each(|target| target_uses(target, names)),
+        Target::Starred(target) => target_uses(target, names),
+    }
+}
+
+fn pattern_uses(pattern: &Pattern, names: &mut HashSet<String>) {
+    match pattern {
+        Pattern::Value(expr) => expression_uses(expr, names),
+        Pattern::Sequence(patterns) | Pattern::Or(patterns) => patterns.iter().for_each(|pattern| pattern_uses(pattern, names)),
+        Pattern::As(pattern, _) => pattern_uses(pattern, names),
+        Pattern::Mapping(items, _) => {
+            for (key, pattern) in items {
+                pattern_uses(key, names);
+                pattern_uses(pattern, names);
+            }
+        }
+        Pattern::Wildcard | Pattern::Capture(_) | Pattern::Literal(_) | Pattern::Star(_) => {}
+    }
+}
+
+/// A Python exception raised while running, like `ZeroDivisionError: division by zero`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Exception {
+    pub kind: String,
+    pub message: String,
+    // the first line of the statement that raised it, set when it leaves the statement
+    pub span: Option<Span>,
+}
+
+impl Exception {
+    pub fn new(kind: &str, message: impl Into<String>) -> Self {
+        Exception { kind: kind.to_string(), message: message.into(), span: None }
+    }
+}
+
+impl fmt::Display for Exception {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.message.as_str() {
+            // like a bare `StopIteration`
+            "" => write!(f, "{}", self.kind),
+            message => write!(f, "{}: {}", self.kind, message),
+        }
+    }
+}
+
+/// How a statement ended. Anything but `Normal` stops the rest of its block
+/// and goes up to the loop, the function or the module that handles it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Flow {
+    Normal,
+    Return(Value),
+    Break,
+    Continue,
+    Exception(Exception),
+}
+
+// Python's default recursion limit
+const MAX_CALL_DEPTH: usize = 1000;
+
+// Define a struct for storing the interpreter state
+#[derive(Clone,Debug)]
+struct Interpreter {
+    globals: Rc<Scope>,
+    // the scope of the running function, the globals in the module
+    scope: Rc<Scope>,
+    // how many function calls are running, to stop runaway recursion
+    depth: usize,
+}
+
+// Implement the Interpreter struct
+impl Interpreter {
+    fn new() -> Self {
+        let globals = Rc::new(Scope::module());
+        Interpreter { scope: Rc::clone(&globals), globals, depth: 0 }
+    }
+
+    // Define a function to evaluate an expression and return its value
+    fn eval_expression(&self, expr: &Expression) -> Result<Value, Exception> {
+        Ok(match expr {
+            Expression::Binary(left, op, right) => {
+                let left_value = self.eval_expression(left)?;
+                match op {
+                    // the right operand is only evaluated when it decides the result
+                    BinaryOperator::And if !left_value.truthy() => left_value,
+                    BinaryOperator::Or if left_value.truthy() => left_value,
+                    BinaryOperator::And | BinaryOperator::Or => self.eval_expression(right)?,
+                    _ => binary_operation(op, left_value, self.eval_expression(right)?)?,
+                }
+            }
+            Expression::Compare(left, comparisons) => {
+                let mut left_value = self.eval_expression(left)?;
+                for (op, right) in comparisons {
+                    let right_value = self.eval_expression(right)?;
+                    if !compare(op, &left_value, &right_value)? {
+                        return Ok(Value::Boolean(false));
+                    }
+                    left_value = right_value;
+                }
+                Value::Boolean(true)
+            }
+            Expression::Unary(op, expr) => {
+                let value = self.eval_expression(expr)?;
+                match (op, &value) {
+                    (UnaryOperator::Not, _) => Value::Boolean(!value.truthy()),
+                    (UnaryOperator::Minus, Value::Float(float)) => Value::Float(-float),
+                    (UnaryOperator::Plus, Value::Float(float)) => Value::Float(*float),
+                    (UnaryOperator::Minus, Value::Complex(real, imaginary)) => Value::Complex(-real, -imaginary),
+                    (UnaryOperator::Plus, Value::Complex(real, imaginary)) => Value::Complex(*real, *imaginary),
+                    (UnaryOperator::Minus, _) if as_integer(&value).is_some() => {
+                        Value::Integer(as_integer(&value).unwrap().checked_neg().ok_or_else(overflow)?)
+                    }
+                    (UnaryOperator::Plus, _) if as_integer(&value).is_some() => Value::Integer(as_integer(&value).unwrap()),
+                    (UnaryOperator::Invert, _) if as_integer(&value).is_some() => Value::Integer(!as_integer(&value).unwrap()),
+                    _ => {
+                        let symbol = match op {
+                            UnaryOperator::Minus => "-",
+                            UnaryOperator::Plus => "+",
+                            _ => "~",
+                        };
+                        let message = format!("bad operand type for unary {}: '{}'", symbol, value.type_name());
+                        return Err(Exception::new("TypeError", message));
+                    }
+                }
+            }
+            Expression::Conditional(test, body, orelse) => {
+                if self.eval_expression(test)?.truthy() {
+                    self.eval_expression(body)?
+                } else {
+                    self.eval_expression(orelse)?
+                }
+            }
+            // a lambda is a function whose body returns its expression
+            Expression::Lambda(params, body) => {
+                let function = Function {
+                    name: String::from("<lambda>"),
+                    params: params.clone(),
+                    body: vec![StatementKind::Return((**body).clone()).into()],
+                };
+                Value::Function(Rc::new(self.make_function(&function)?))
+            }
+            Expression::Await(_expr) => return Err(Exception::new("SyntaxError", "'await' outside async function")),
+            Expression::Tuple(items) => Value::Tuple(self.eval_items(items)?),
+            Expression::List(items) => Value::List(self.eval_items(items)?),
+            // repeated items are only kept once
+            Expression::Set(items) => {
+                let mut set: Vec<Value> = Vec::new();
+                for item in items {
+                    let value = self.eval_expression(item)?;
+                    if !set.iter().any(|other| values_equal(other, &value)) {
+                        set.push(value);
+                    }
+                }
+                Value::Set(set)
+            }
+            // a repeated key keeps its first position and its last value
+            Expression::Dict(items) => {
+                let mut dict: Vec<(Value, Value)> = Vec::new();
+                for (key, value) in items {
+                    let key = self.eval_expression(key)?;
+                    let value = self.eval_expression(value)?;
+                    match dict.iter_mut().find(|(other, _)| values_equal(other, &key)) {
+                        Some(item) => item.1 = value,
+                        None => dict.push((key, value)),
+                    }
+                }
+                Value::Dict(dict)
+            }
+            Expression::Attribute(expr, name) => return self.get_attribute(self.eval_expression(expr)?, name),
+            Expression::Subscript(expr, index) => subscript(&self.eval_expression(expr)?, &self.eval_expression(index)?)?,
+            Expression::Literal(value) => value.clone(),
+            Expression::LargeInteger(literal) => {
+                return Err(Exception::new("OverflowError", format!("integer {} is too large for this interpreter", literal)));
+            }
+            Expression::JoinedStr(parts) => {
+                let mut text = String::new();
+                for part in parts {
+                    text.push_str(&self.eval_expression(part)?.to_string());
+                }
+                Value::String(text)
+            }
+            Expression::FormattedValue(expr, conversion, format_spec) => {
+                let value = self.eval_expression(expr)?;
+                let value = match conversion {
+                    Some('s') => Value::String(value.to_string()),
+                    Some('r') => Value::String(value.repr()),
+                    Some('a') => Value::String(ascii(&value.repr())),
+                    _ => value,
+                };
+                let spec = match format_spec {
+                    Some(spec) => self.eval_expression(spec)?.to_string(),
+                    None => String::new(),
+                };
+                match format_value(&value, &spec) {
+                    Ok(text) => Value::String(text),
+                    // a spec the type doesn't know is a ValueError, a spec given to a type without any is a TypeError
+                    Err(message) if message.starts_with("unsupported format string") => {
+                        return Err(Exception::new("TypeError", message));
+                    }
+                    Err(message) => return Err(Exception::new("ValueError", message)),
+                }
+            }
+            Expression::Variable(name) => self.lookup(name)?,
+            Expression::FunctionCall(callee, args) => {
+                let callee = self.eval_expression(callee)?;
+                let name = match &callee {
+                    Value::Function(function) => function.function.name.clone(),
+                    Value::Builtin(builtin) => builtin.name().to_string(),
+                    value => return Err(Exception::new("TypeError", format!("'{}' object is not callable", value.type_name()))),
+                };
+                let (positional, keywords) = self.eval_arguments(&name, args)?;
+                self.call(&callee, positional, keywords)?
+            }
+        })
+    }
+
+    // call a value checked to be callable, see `is_callable`
+    fn call(&self, callee: &Value, positional: Vec<Value>, keywords: Vec<(String, Value)>) -> Result<Value, Exception> {
+        match callee {
+            Value::Function(function) => self.call_function(function, positional, keywords),
+            Value::Builtin(builtin) => self.call_builtin(*builtin, positional, keywords),
+            _ => unreachable!("only functions and builtins can be called"),
+        }
+    }
+
+    // only type aliases have attributes
+    fn get_attribute(&self, object: Value, name: &str) -> Result<Value, Exception> {
+        let Value::TypeAlias(alias) = &object else {
+            return Err(no_attribute(&object, name));
+        };
+        let parameters = || alias.parameters.iter().cloned().map(Value::String).collect::<Vec<_>>();
+        match name {
+            "__name__" => Ok(Value::String(alias.name.clone())),
+            "__type_params__" => Ok(Value::Tuple(parameters())),
+            "__value__" => self.call_function(&alias.value, parameters(), Vec::new()),
+            _ => Err(no_attribute(&object, name)),
+        }
+    }
+
+    // Run the body of the function with the arguments bound to its parameters
+    fn call_function(&self, function: &FunctionObject, positional: Vec<Value>, keywords: Vec<(String, Value)>) -> Result<Value, Exception> {
+        if self.depth >= MAX_CALL_DEPTH {
+            return Err(Exception::new("RecursionError", "maximum recursion depth exceeded"));
+        }
+        let mut variables = bind_arguments(function, positional, keywords)?;
+        for name in &function.locals {
+            variables.entry(name.clone()).or_insert_with(|| new_cell(None));
+        }
+        let scope = Scope { variables: RefCell::new(variables), globals: function.globals.clone(), free: function.free.clone() };
+        let mut interpreter = Interpreter { globals: Rc::clone(&self.globals), scope: Rc::new(scope), depth: self.depth + 1 };
+        // falling off the end of the body returns None
+        match interpreter.eval_block(&function.function.body) {
+            Flow::Normal => Ok(Value::None),
+            Flow::Return(value) => Ok(value),
+            Flow::Exception(exception) => Err(exception),
+            Flow::Break => Err(Exception::new("SyntaxError", "'break' outside loop")),
+            Flow::Continue => Err(Exception::new("SyntaxError", "'continue' not properly in loop")),
+        }
+    }
+
+    fn call_builtin(&self, builtin: Builtin, positional: Vec<Value>, keywords: Vec<(String, Value)>) -> Result<Value, Exception> {
+        let name = builtin.name();
+        let type_error = |message: String| Err(Exception::new("TypeError", message));
+        if !keywords.is_empty() {
+            return type_error(format!("{}() takes no keyword arguments", name));
+        }
+        let (min, max) = match builtin {
+            Builtin::Range => (1, 3),
+            Builtin::Iter | Builtin::Next => (1, 2),
+        };
+        if positional.len() < min {
+            return type_error(format!("{} expected at least {} argument, got {}", name, min, positional.len()));
+        } else if positional.len() > max {
+            return type_error(format!("{} expected at most {} arguments, got {}", name, max, positional.len()));
+        }
+        match builtin {
+            Builtin::Range => {
+                let mut args = Vec::new();
+                for arg in &positional {
+                    match as_integer(arg) {
+                        Some(integer) => args.push(integer),
+                        None => return type_error(format!("'{}' object cannot be interpreted as an integer", arg.type_name())),
+                    }
+                }
+                let (start, stop, step) = match args[..] {
+                    [stop] => (0, stop, 1),
+                    [start, stop] => (start, stop, 1),
+                    [start, stop, step] => (start, stop, step),
+                    _ => unreachable!("the number of arguments is checked above"),
+                };
+                if step == 0 {
+                    return Err(Exception::new("ValueError", "range() arg 3 must not be zero"));
+                }
+                Ok(Value::Range(start, stop, step))
+            }
+            Builtin::Iter => match positional.as_slice() {
+                [value] => match make_iterator(value) {
+                    Some(iterator) => Ok(Value::Iterator(iterator)),
+                    None => type_error(format!("'{}' object is not iterable", value.type_name())),
+                },
+                [callable, sentinel] if is_callable(callable) => {
+                    let state = IteratorState::Callable(callable.clone(), sentinel.clone());
+                    Ok(Value::Iterator(Rc::new(IteratorObject { type_name: "callable_iterator", state: RefCell::new(state) })))
+                }
+                _ => type_error("iter(v, w): v must be callable".to_string()),
+            },
+            Builtin::Next => {
+                let Value::Iterator(iterator) = &positional[0] else {
+                    return type_error(format!("'{}' object is not an iterator", positional[0].type_name()));
+                };
+                match (self.next_item(iterator)?, positional.get(1)) {
+                    (Some(item), _) => Ok(item),
+                    (None, Some(default)) => Ok(default.clone()),
+                    (None, None) => Err(Exception::new("StopIteration", "")),
+                }
+            }
+        }
+    }
+
+    // Next item of the iterator, None once it's exhausted
+    fn next_item(&self, iterator: &IteratorObject) -> Result<Option<Value>, Exception> {
+        let mut state = iterator.state.borrow_mut();
+        let (callable, sentinel) = match &mut *state {
+            IteratorState::Items(items, position) => {
+                let item = items.get(*position).cloned();
+                *position = (*position + 1).min(items.len());
+                return Ok(item);
+            }
+            IteratorState::Range { next, stop, step } => {
+                if (*step > 0 && *next >= *stop) || (*step < 0 && *next <= *stop) {
+                    return Ok(None);
+                }
+                let item = *next;
+                // saturating keeps the next value past the stop instead of wrapping around
+                *next = next.saturating_add(*step);
+                return Ok(Some(Value::Integer(item)));
+            }
+            IteratorState::Callable(callable, sentinel) => (callable.clone(), sentinel.clone()),
+            IteratorState::Exhausted => return Ok(None),
+        };
+        // the function may use the iterator too, so it's not borrowed during the call
+        drop(state);
+        let value = self.call(&callable, Vec::new(), Vec::new())?;
+        if values_equal(&value, &sentinel) {
+            *iterator.state.borrow_mut() = IteratorState::Exhausted;
+            return Ok(None);
+        }
+        Ok(Some(value))
+    }
+
+    // all the items left in the iterator
+    fn collect_items(&self, iterator: &IteratorObject) -> Result<Vec<Value>, Exception> {
+        let mut items = Vec::new();
+        while let Some(item) = self.next_item(iterator)? {
+            items.push(item);
+        }
+        Ok(items)
+    }
+
+    fn eval_items(&self, items: &[Expression]) -> Result<Vec<Value>, Exception> {
+        items.iter().map(|item| self.eval_expression(item)).collect()
+    }
+
+    // Values of the positional and of the keyword arguments of a call, with
+    // `*iterable` and `**mapping` unpacked
+    fn eval_arguments(&self, name: &str, args: &[Argument]) -> Result<Arguments, Exception> {
+        let mut positional = Vec::new();
+        let mut keywords: Vec<(String, Value)> = Vec::new();
+        let mut add_keyword = |keyword: String, value: Value| {
+            if keywords.iter().any(|(other, _)| *other == keyword) {
+                let message = format!("{}() got multiple values for keyword argument '{}'", name, keyword);
+                return Err(Exception::new("TypeError", message));
+            }
+            keywords.push((keyword, value));
+            Ok(())
+        };
+        for arg in args {
+            match arg {
+                Argument::Positional(expr) => positional.push(self.eval_expression(expr)?),
+                Argument::Keyword(keyword, expr) => add_keyword(keyword.clone(), self.eval_expression(expr)?)?,
+                Argument::Unpack(expr) => {
+                    let value = self.eval_expression(expr)?;
+                    match make_iterator(&value) {
+                        Some(iterator) => positional.extend(self.collect_items(&iterator)?),
+                        None => {
+                            let message = format!("{}() argument after * must be an iterable, not {}", name, value.type_name());
+                            return Err(Exception::new("TypeError", message));
+                        }
+                    }
+                }
+                Argument::UnpackMapping(expr) => match self.eval_expression(expr)? {
+                    Value::Dict(items) => {
+                        for (key, value) in items {
+                            let Value::String(keyword) = key else {
+                                return Err(Exception::new("TypeError", "keywords must be strings"));
+                            };
+                            add_keyword(keyword, value)?;
+                        }
+                    }
+                    value => {
+                        let message = format!("{}() argument after ** must be a mapping, not {}", name, value.type_name());
+                        return Err(Exception::new("TypeError", message));
+                    }
+                },
+            }
+        }
+        Ok((positional, keywords))
+    }
+
+    // The value of a `def` or a lambda, its defaults are evaluated once, now.
+    // Its local names are found here, and when it's defined in a function it
+    // takes the cells of its free variables from the current call.
+    fn make_function(&self, function: &Function) -> Result<FunctionObject, Exception> {
+        let defaults = function
+            .params
+            .iter()
+            .map(|param| param.default.as_ref().map(|default| self.eval_expression(default)).transpose())
+            .collect::<Result<_, _>>()?;
+        let (mut locals, globals, nonlocals) = local_names(function);
+        let mut free = HashMap::new();
+        for name in free_names(function) {
+            if let Binding::Local(cell) | Binding::Free(cell) = self.resolve(&name) {
+                free.insert(name, cell);
+            }
+        }
+        let syntax_error = |message: String| Err(Exception::new("SyntaxError", message));
+        for param in &function.params {
+            if globals.contains(&param.name) {
+                return syntax_error(format!("name '{}' is parameter and global", param.name));
+            } else if nonlocals.contains(&param.name) {
+                return syntax_error(format!("name '{}' is parameter and nonlocal", param.name));
+            }
+        }
+        for name in &nonlocals {
+            if globals.contains(name) {
+                return syntax_error(format!("name '{}' is nonlocal and global", name));
+            }
+            // the binding must be a local of an enclosing function
+            if !free.contains_key(name) {
+                return syntax_error(format!("no binding for nonlocal '{}' found", name));
+            }
+        }
+        locals.retain(|name| !globals.contains(name) && !nonlocals.contains(name));
+        Ok(FunctionObject { function: function.clone(), defaults, locals, globals, free })
+    }
+
+    // Run the statements in order, up to the first one that doesn't end normally
+    fn eval_block(&mut self, statements: &[Statement]) -> Flow {
+        for statement in statements {
+            match self.eval_statement(statement) {
+                Flow::Normal => {}
+                flow => return flow,
+            }
+        }
+        Flow::Normal
+    }
+
+    // Define a function to evaluate a statement. An exception raised by the
+    // statement itself gets its place, one coming out of a call already has
+    // the place of the statement of the function that raised it.
+    fn eval_statement(&mut self, statement: &Statement) -> Flow {
+        self.execute(statement).unwrap_or_else(|mut exception| {
+            exception.span = exception.span.or(statement.span);
+            Flow::Exception(exception)
+        })
+    }
+
+    // an exception raised by an expression ends the statement
+    fn execute(&mut self, statement: &Statement) -> Result<Flow, Exception> {
+        match &statement.kind {
+            StatementKind::Import(_name) => {}
+            StatementKind::Assignment(targets, expr) => {
+                let value = self.eval_expression(expr)?;
+                for target in targets {
+                    self.assign(target, value.clone())?;
+                }
+            }
+            // the object and the index of the target are evaluated once
+            StatementKind::AugmentedAssignment(target, op, expr) => match target {
+                Target::Name(name) => {
+                    let value = self.lookup(name)?;
+                    let value = binary_operation(op, value, self.eval_expression(expr)?)?;
+                    self.set_variable(name, value);
+                }
+                Target::Subscript(object, index) => {
+                    let (root, mut root_value, keys) = self.item_place(object, index)?;
+                    let value = item_mut(&mut root_value, &keys)?.clone();
+                    let value = binary_operation(op, value, self.eval_expression(expr)?)?;
+                    *item_mut(&mut root_value, &keys)? = value;
+                    self.store_root(root, root_value)?;
+                }
+                Target::Attribute(object, name) => return Err(no_attribute(&self.eval_expression(object)?, name)),
+                Target::Sequence(_) | Target::Starred(_) => {
+                    return Err(Exception::new("SyntaxError", "illegal expression for augmented assignment"));
+                }
+            },
+            StatementKind::Expression(expr) => {
+                self.eval_expression(expr)?;
+            }
+            // an `elif` is an If alone in the else block, the chain is run in a
+            // loop so a long one doesn't use the stack
+            StatementKind::If(cond, if_block, else_block) => {
+                let (mut cond, mut if_block, mut else_block, mut span) = (cond, if_block, else_block, statement.span);
+                loop {
+                    let cond_value = self.eval_expression(cond).map_err(|mut exception| {
+                        exception.span = exception.span.or(span);
+                        exception
+                    })?;
+                    if cond_value.truthy() {
+                        return Ok(self.eval_block(if_block));
+                    }
+                    match else_block.as_deref() {
+                        Some([Statement { kind: StatementKind::If(elif_cond, elif_block, elif_else), span: elif_span }]) => {
+                            (cond, if_block, else_block, span) = (elif_cond, elif_block, elif_else, *elif_span);
+                        }
+                        Some(block) => return Ok(self.eval_block(block)),
+                        None => break,
+                    }
+                }
+            }
+            StatementKind::While(cond, block, else_block) => {
+                while self.eval_expression(cond)?.truthy() {
+                    match self.eval_block(block) {
+                        Flow::Normal | Flow::Continue => {}
+                        Flow::Break => return Ok(Flow::Normal),
+                        flow => return Ok(flow),
+                    }
+                }
+                // the else block only runs when the loop wasn't broken out of
+                if let Some(block) = else_block {
+                    return Ok(self.eval_block(block));
+                }
+            }
+            StatementKind::For(target, iterable, block, else_block) => {
+                let iterable = self.eval_expression(iterable)?;
+                let Some(iterator) = make_iterator(&iterable) else {
+                    return Err(Exception::new("TypeError", format!("'{}' object is not iterable", iterable.type_name())));
+                };
+                while let Some(item) = self.next_item(&iterator)? {
+                    self.assign(target, item)?;
+                    match self.eval_block(block) {
+                        Flow::Normal | Flow::Continue => {}
+                        Flow::Break => return Ok(Flow::Normal),
+                        flow => return Ok(flow),
+                    }
+                }
+                if let Some(block) = else_block {
+                    return Ok(self.eval_block(block));
+                }
+            }
+            StatementKind::Function(function) => {
+                let object = self.make_function(function)?;
+                self.set_variable(&function.name, Value::Function(Rc::new(object)));
+            }
+            StatementKind::Return(expr) => return Ok(Flow::Return(self.eval_expression(expr)?)),
+            // the declarations were read when the function was defined
+            StatementKind::Pass | StatementKind::Global(_) | StatementKind::Nonlocal(_) => {}
+            StatementKind::Break => return Ok(Flow::Break),
+            StatementKind::Continue => return Ok(Flow::Continue),
+            StatementKind::Match(subject, cases) => {
+                let value = self.eval_expression(subject)?;
+                for case in cases {
+                    let mut bindings = Vec::new();
+                    if !self.match_pattern(&case.pattern, &value, &mut bindings)? {
+                        continue;
+                    }
+                    // names are bound before the guard runs, even if it fails
+                    for (name, value) in bindings {
+                        self.set_variable(&name, value);
+                    }
+                    if let Some(guard) = &case.guard {
+                        if !self.eval_expression(guard)?.truthy() {
+                            continue;
+                        }
+                    }
+                    return Ok(self.eval_block(&case.body));
+                }
+            }
+            StatementKind::TypeAlias(name, parameters, value) => {
+                let function = Function {
+                    name: name.clone(),
+                    params: parameters
+                        .iter()
+                        .map(|parameter| Parameter { name: parameter.clone(), kind: ParameterKind::PositionalOnly, default: None })
+                        .collect(),
+                    body: vec![StatementKind::Return(value.clone()).into()],
+                };
+                let value = Rc::new(self.make_function(&function)?);
+                let alias = TypeAliasObject { name: name.clone(), parameters: parameters.clone(), value };
+                self.set_variable(name, Value::TypeAlias(Rc::new(alias)));
+            }
+            StatementKind::Error => return Err(Exception::new("SyntaxError", "invalid syntax")),
+        }
+        Ok(Flow::Normal)
+    }
+
+    // Check the value against the pattern, collecting the names it binds.
+    // Value patterns are evaluated when they are reached.
+    fn match_pattern(&self, pattern: &Pattern, value: &Value, bindings: &mut Vec<(String, Value)>) -> Result<bool, Exception> {
+        Ok(match pattern {
+            Pattern::Wildcard => true,
+            Pattern::Capture(name) => {
+                bindings.push((name.clone(), value.clone()));
+                true
+            }
+            // None, True and False match by identity, numbers and strings with `==`
+            Pattern::Literal(literal @ (Value::None | Value::Boolean(_))) => literal == value,
+            Pattern::Literal(literal) => values_equal(literal, value),
+            Pattern::Value(expr) => values_equal(&self.eval_expression(expr)?, value),
+            Pattern::As(pattern, name) => {
+                if !self.match_pattern(pattern, value, bindings)? {
+                    return Ok(false);
+                }
+                bindings.push((name.clone(), value.clone()));
+                true
+            }
+            // only the bindings of the alternative that matched are kept
+            Pattern::Or(alternatives) => {
+                for alternative in alternatives {
+                    let mut alternative_bindings = Vec::new();
+                    if self.match_pattern(alternative, value, &mut alternative_bindings)? {
+                        bindings.extend(alternative_bindings);
+                        return Ok(true);
+                    }
+                }
+                false
+            }
+            // strings are sequences but never match sequence patterns
+            Pattern::Sequence(patterns) => {
+                let items = match value {
+                    Value::List(items) | Value::Tuple(items) => items,
+                    _ => return Ok(false),
+                };
+                let star = patterns.iter().position(|pattern| matches!(pattern, Pattern::Star(_)));
+                let Some(star) = star else {
+                    if items.len() != patterns.len() {
+                        return Ok(false);
+                    }
+                    return self.match_all(patterns.iter().zip(items), bindings);
+                };
+                let after = patterns.len() - star - 1;
+                if items.len() < star + after {
+                    return Ok(false);
+                }
+                let rest = &items[star..items.len() - after];
+                let pairs = patterns[..star].iter().zip(items).chain(patterns[star + 1..].iter().zip(&items[items.len() - after..]));
+                if !self.match_all(pairs, bindings)? {
+                    return Ok(false);
+                }
+                if let Pattern::Star(Some(name)) = &patterns[star] {
+                    bindings.push((name.clone(), Value::List(rest.to_vec())));
+                }
+                true
+            }
+            Pattern::Star(_) => false,
+            // the keys have to be present, `**rest` gets a dict of the other items
+            Pattern::Mapping(items, rest) => {
+                let Value::Dict(dict) = value else {
+                    return Ok(false);
+                };
+                let mut keys = Vec::new();
+                for (key, pattern) in items {
+                    let key = match key {
+                        Pattern::Literal(key) => key.clone(),
+                        Pattern::Value(expr) => self.eval_expression(expr)?,
+                        _ => unreachable!("mapping keys are literals or values"),
+                    };
+                    let Some(item) = dict_get(dict, &key) else {
+                        return Ok(false);
+                    };
+                    if !self.match_pattern(pattern, item, bindings)? {
+                        return Ok(false);
+                    }
+                    keys.push(key);
+                }
+                if let Some(name) = rest {
+                    let others = dict.iter().filter(|(key, _)| !keys.iter().any(|other| values_equal(key, other))).cloned().collect();
+                    bindings.push((name.clone(), Value::Dict(others)));
+                }
+                true
+            }
+        })
+    }
+
+    // match each pattern against its value, stopping at the first that fails
+    fn match_all<'a>(
+        &self,
+        pairs: impl Iterator<Item = (&'a Pattern, &'a Value)>,
+        bindings: &mut Vec<(String, Value)>,
+    ) -> Result<bool, Exception> {
+        for (pattern, value) in pairs {
+            if !self.match_pattern(pattern, value, bindings)? {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    // Store the value in the target, unpacking it for `a, b` or `first, *rest`
+    fn assign(&mut self, target: &Target, value: Value) -> Result<(), Exception> {
+        let targets = match target {
+            Target::Name(name) => {
+                self.set_variable(name, value);
+                return Ok(());
+            }
+            Target::Attribute(object, name) => return Err(no_attribute(&self.eval_expression(object)?, name)),
+            Target::Subscript(object, index) => {
+                let (root, mut root_value, keys) = self.item_place(object, index)?;
+                let (last, path) = keys.split_last().unwrap();
+                set_item(item_mut(&mut root_value, path)?, last, value)?;
+                return self.store_root(root, root_value);
+            }
+            Target::Sequence(targets) => targets,
+            // the parser doesn't make these, but an AST built by hand can have them
+            Target::Starred(_) => {
+                return Err(Exception::new("SyntaxError", "starred assignment target must be in a list or tuple"));
+            }
+        };
+        if targets.iter().filter(|target| matches!(target, Target::Starred(_))).count() > 1 {
+            return Err(Exception::new("SyntaxError", "multiple starred expressions in assignment"));
+        }
+        let Some(iterator) = make_iterator(&value) else {
+            return Err(Exception::new("TypeError", format!("cannot unpack non-iterable {} object", value.type_name())));
+        };
+        let mut items = self.collect_items(&iterator)?;
+        let value_error = |message: String| Err(Exception::new("ValueError", message));
+        let star = targets.iter().position(|target| matches!(target, Target::Starred(_)));
+        let rest = match star {
+            None if items.len() > targets.len() => {
+                return value_error(format!("too many values to unpack (expected {})", targets.len()));
+            }
+            None if items.len() < targets.len() => {
+                return value_error(format!("not enough values to unpack (expected {}, got {})", targets.len(), items.len()));
+            }
+            None => Vec::new(),
+            Some(_) if items.len() < targets.len() - 1 => {
+                let message = format!("not enough values to unpack (expected at least {}, got {})", targets.len() - 1, items.len());
+                return value_error(message);
+            }
+            Some(star) => {
+                let after = targets.len() - star - 1;
+                items.drain(star..items.len() - after).collect()
+            }
+        };
+        let mut items = items.into_iter();
+        let mut rest = Some(Value::List(rest));
+        for target in targets {
+            match target {
+                Target::Starred(target) => self.assign(target, rest.take().unwrap())?,
+                target => self.assign(target, items.next().unwrap())?,
+            }
+        }
+        Ok(())
+    }
+
+    // The value an item assignment like `a[i][j] = value` changes: the
+    // expression it comes from, its value, and the indices from the outside
+    // in. They are evaluated once, in the order of the source.
+    fn item_place<'a>(&self, object: &'a Expression, index: &'a Expression) -> Result<(&'a Expression, Value, Vec<Value>), Exception> {
+        let mut indices = vec![index];
+        let mut root = object;
+        while let Expression::Subscript(inner, index) = root {
+            indices.push(index);
+            root = inner;
+        }
+        let root_value = self.eval_expression(root)?;
+        let keys = indices.iter().rev().map(|index| self.eval_expression(index)).collect::<Result<_, _>>()?;
+        Ok((root, root_value, keys))
+    }
+
+    // Put a changed container back where it was read from. Lists and dicts
+    // are values here, so `a[0] = 1` changes a copy that is stored again in
+    // `a`. A temporary like `f()[0] = 1` is dropped, like in Python.
+    fn store_root(&mut self, root: &Expression, value: Value) -> Result<(), Exception> {
+        match root {
+            Expression::Variable(name) => {
+                self.set_variable(name, value);
+                Ok(())
+            }
+            Expression::Attribute(object, name) => Err(no_attribute(&self.eval_expression(object)?, name)),
+            _ => Ok(()),
+        }
+    }
+
+    // Find a name with the LEGB rules: a local of the running function, a
+    // variable of an enclosing one, then a global. In the module every name
+    // is global.
+    fn resolve(&self, name: &str) -> Binding {
+        let scope = &self.scope;
+        if Rc::ptr_eq(scope, &self.globals) || scope.globals.contains(name) {
+            return Binding::Global;
+        }
+        if let Some(cell) = scope.cell(name) {
+            return Binding::Local(cell);
+        }
+        match scope.free.get(name) {
+            Some(cell) => Binding::Free(Rc::clone(cell)),
+            None => Binding::Global,
+        }
+    }
+
+    fn lookup(&self, name: &str) -> Result<Value, Exception> {
+        let (cell, kind, message) = match self.resolve(name) {
+            Binding::Local(cell) => {
+                (cell, "UnboundLocalError", format!("cannot access local variable '{}' where it is not associated with a value", name))
+            }
+            Binding::Free(cell) => {
+                let message = format!("cannot access free variable '{}' where it is not associated with a value in enclosing scope", name);
+                (cell, "NameError", message)
+            }
+            // the builtins come after the globals
+            Binding::Global => match self.globals.cell(name).and_then(|cell| cell.borrow().clone()) {
+                Some(value) => return Ok(value),
+                None => match Builtin::from_name(name) {
+                    Some(builtin) => return Ok(Value::Builtin(builtin)),
+                    None => return Err(Exception::new("NameError", format!("name '{}' is not defined", name))),
+                },
+            },
+        };
+        let value = cell.borrow().clone();
+        value.ok_or_else(|| Exception::new(kind, message))
+    }
+
+    fn set_variable(&mut self, name: &str, value: Value) {
+        match self.resolve(name) {
+            Binding::Local(cell) | Binding::Free(cell) => *cell.borrow_mut() = Some(value),
+            Binding::Global => match self.globals.cell(name) {
+                Some(cell) => *cell.borrow_mut() = Some(value),
+                None => {
+                    self.globals.variables.borrow_mut().insert(name.to_string(), new_cell(Some(value)));
+                }
+            },
+        }
+    }
+}
+
+/// Run the statements of a module, giving the exception that stopped it.
+pub fn run(statements: &[Statement]) -> Result<(), Exception> {
+    let mut interpreter = Interpreter::new();
+    match interpreter.eval_block(statements) {
+        Flow::Exception(exception) => Err(exception),
+        Flow::Return(_) => Err(Exception::new("SyntaxError", "'return' outside function")),
+        Flow::Break => Err(Exception::new("SyntaxError", "'break' outside loop")),
+        Flow::Continue => Err(Exception::new("SyntaxError", "'continue' not properly in loop")),
+        Flow::Normal => Ok(()),
+    }
+}
+
+
+#[allow(dead_code)]
+pub fn main() {
+    // Create an instance of the interpreter
+    let mut interpreter = Interpreter::new();
+
+    // dbg!(Expression::Literal(Value::Integer(10)));
+    
+    let function = Function {
+        name: String::from("add"),
+        params: vec![
+            Parameter { name: String::from("a"), kind: ParameterKind::PositionalOrKeyword, default: None },
+            Parameter { name: String::from("b"), kind: ParameterKind::PositionalOrKeyword, default: None },
+        ],
+        body: vec![
+            StatementKind::Expression(Expression::Variable(String::from("a"))).into(),
+            StatementKind::Expression(Expression::Variable(String::from("b"))).into(),
+            StatementKind::Return(Expression::Literal(Value::None)).into(),
+        ],
+    };
+
+    // Example code
+    let statements = vec![
+        StatementKind::Function(function).into(),
+        // StatementKind::If(
+        //     Expression::Binary(
+        //         Box::new(Expression::Literal(Value::Integer(10))),
+        //         BinaryOperator::NotEqual,
+        //         Box::new(Expression::Literal(Value::Integer(20))),
+        //     ),
+        //     vec![StatementKind::Assignment(String::from("x"),Expression::Literal(Value::Float(3.6)))],
+        //     Some(vec![StatementKind::Assignment(String::from("x"),Expression::Literal(Value::Float(33.6)))]),
+        // ),
+        // StatementKind::Assignment(
+        //     String::from("u"),
+        //     Expression::Binary(
+        //         Box::new(Expression::Literal(Value::Integer(17))),
+        //         BinaryOperator::NotEqual,
+        //         Box::new(Expression::Literal(Value::Integer(2))),
+        //     ),
+        // ),
+        // StatementKind::Expression(Expression::FunctionCall("add".to_string(),vec![Expression::Literal(Value::Float(4.6)),Expression::Literal(Value::Float(43.6))])),
+        StatementKind::Expression(Expression::Literal(Value::Float(43.6))).into(),
+        // StatementKind::Expression(Expression::Literal(Value::String("x".to_string()))),
+    ];
+
+    // Evaluate the statements
+    for statement in statements {
+        interpreter.eval_statement(&statement);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+    use crate::tokenizer::{tokenize_with, TokenizerOptions};
+
+    // run the source as a module, giving the interpreter and how it ended
+    fn run_source(source: &str) -> (Interpreter, Flow) {
+        let (tokens, errors) = tokenize_with(source, TokenizerOptions::default());
+        assert!(errors.is_empty(), "{:?}", errors);
+        let statements = Parser::new(tokens).parse_tokens().unwrap();
+        let mut interpreter = Interpreter::new();
+        let flow = interpreter.eval_block(&statements);
+        (interpreter, flow)
+    }
+
+    fn global(interpreter: &Interpreter, name: &str) -> Option<Value> {
+        interpreter.globals.cell(name).and_then(|cell| cell.borrow().clone())
+    }
+
+    #[test]
+    fn nested_starred_target_is_an_exception() {
+        let mut interpreter = Interpreter::new();
+        let nested = Target::Starred(Box::new(Target::Starred(Box::new(Target::Name("b".to_string())))));
+        let target = Target::Sequence(vec![Target::Name("a".to_string()), nested]);
+        let value = Value::List(vec![Value::Integer(1), Value::Integer(2)]);
+        assert_eq!(interpreter.assign(&target, value).unwrap_err().kind, "SyntaxError");
+
+        let (interpreter, flow) = run_source("for a, *b in [[1, 2, 3]]:\n    pass\n");
+        assert_eq!(flow, Flow::Normal);
+        assert_eq!(global(&interpreter, "b"), Some(Value::List(vec![Value::Integer(2), Value::Integer(3)])));
+    }
+
+    #[test]
+    fn long_elif_chain() {
+        let mut source = String::from("x = 1500\nif x == 0:\n    y = 0\n");
+        for arm in 1..2000 {
+            source.push_str(&format!("elif x == {}:\n    y = {}\n", arm, arm));
+        }
+        source.push_str("else:\n    y = -1\n");
+        let (interpreter, flow) = run_source(&source);
+        assert_eq!(flow, Flow::Normal);
+        assert_eq!(global(&interpreter, "y"), Some(Value::Integer(1500)));
+    }
+
+    #[test]
+    fn assignment_forms() {
+        let source = "a, (b, *c) = 1, [2, 3, 4]\nx = y = 5\nx += 1\ny **= 2\nd = {'k': [1, 2]}\nd['k'][0] += 10\nd['n'] = 0\nl = [[0, 0], (0, [0])]\nl[1][1][0] = 'z'\nl[-2][1] = 7\n";
+        let (interpreter, flow) = run_source(source);
+        assert_eq!(flow, Flow::Normal);
+        let value = |name| global(&interpreter, name).map(|value| value.repr());
+        assert_eq!(value("a"), Some("1".to_string()));
+        assert_eq!(value("b"), Some("2".to_string()));
+        assert_eq!(value("c"), Some("[3, 4]".to_string()));
+        assert_eq!(value("x"), Some("6".to_string()));
+        assert_eq!(value("y"), Some("25".to_string()));
+        assert_eq!(value("d"), Some("{'k': [11, 2], 'n': 0}".to_string()));
+        assert_eq!(value("l"), Some("[[0, 7], (0, ['z'])]".to_string()));
+    }
+
+    #[test]
+    fn assignment_errors() {
+        let error = |source| match run_source(source).1 {
+            Flow::Exception(exception) => exception.to_string(),
+            flow => panic!("expected an exception, got {:?}", flow),
+        };
+        assert_eq!(error("t = (1, 2)\nt[0] = 3\n"), "TypeError: 'tuple' object does not support item assignment");
+        assert_eq!(error("l = [1]\nl[1] = 3\n"), "IndexError: list assignment index out of range");
+        assert_eq!(error("d = {}\nd['k'] += 1\n"), "KeyError: 'k'");
+        assert_eq!(error("x = 1\nx.attr = 2\n"), "AttributeError: 'int' object has no attribute 'attr'");
+        assert_eq!(error("z += 1\n"), "NameError: name 'z' is not defined");
+        assert_eq!(error("def f():\n    n += 1\nf()\n").split(':').next(), Some("UnboundLocalError"));
+    }
+
+    #[test]
+    fn runaway_recursion_is_a_recursion_error() {
+        // the interpreter runs on a big stack in main too
+        let result = std::thread::Builder::new().stack_size(256 << 20).spawn(|| {
+            let (interpreter, flow) = run_source("def f(n):\n    return f(n + 1)\ndef g(n):\n    if n == 0:\n        return 'done'\n    return g(n - 1)\nx = g(900)\nf(0)\n");
+            (global(&interpreter, "x").map(|value| value.repr()), format!("{:?}", flow))
+        }).unwrap().join().unwrap();
+        assert_eq!(result.0, Some("'done'".to_string()));
+        assert!(result.1.contains("maximum recursion depth exceeded"), "{}", result.1);
+    }
+
+    #[test]
+    fn large_integer_fails_when_evaluated() {
+        let (interpreter, flow) = run_source("x = 1\nif x == 2:\n    x = 18446744073709551616\n");
+        assert_eq!(flow, Flow::Normal);
+        assert_eq!(global(&interpreter, "x"), Some(Value::Integer(1)));
+        match run_source("x = -0xFFFF_FFFF_FFFF_FFFF\n").1 {
+            Flow::Exception(exception) => assert_eq!(exception.kind, "OverflowError"),
+            flow => panic!("expected an OverflowError, got {:?}", flow),
+        }
+    }
+
+    #[test]
+    fn powers_of_zero_and_negative_numbers() {
+        let (interpreter, flow) = run_source("a = 2 ** -1\nb = (-8) ** 0.5\nc = (-8.0) ** 2.0\nd = 0.0 ** 0\n");
+        assert_eq!(flow, Flow::Normal);
+        assert_eq!(global(&interpreter, "a"), Some(Value::Float(0.5)));
+        assert!(matches!(global(&interpreter, "b"), Some(Value::Complex(real, imaginary)) if real.abs() < 1e-12 && (imaginary - 8f64.sqrt()).abs() < 1e-12));
+        assert_eq!(global(&interpreter, "c"), Some(Value::Float(64.0)));
+        assert_eq!(global(&interpreter, "d"), Some(Value::Float(1.0)));
+        for source in ["0 ** -1\n", "0.0 ** -1\n", "False ** -2.5\n"] {
+            match run_source(source).1 {
+                Flow::Exception(exception) => assert_eq!(exception.to_string(), "ZeroDivisionError: 0.0 cannot be raised to a negative power"),
+                flow => panic!("expected a ZeroDivisionError for {:?}, got {:?}", source, flow),
+            }
+        }
+    }
+
+    #[test]
+    fn type_alias_is_evaluated_lazily() {
+        let source = "type X = Later\nLater = 1\nname = X.__name__\nvalue = X.__value__\ndef f():\n    type Pair[T] = (T, n)\n    n = 3\n    return Pair\npair = f().__value__\nparams = f().__type_params__\n";
+        let (interpreter, flow) = run_source(source);
+        assert_eq!(flow, Flow::Normal);
+        let value = |name| global(&interpreter, name).map(|value| value.repr());
+        assert_eq!(value("X"), Some("X".to_string()));
+        assert_eq!(value("name"), Some("'X'".to_string()));
+        assert_eq!(value("value"), Some("1".to_string()));
+        assert_eq!(value("pair"), Some("('T', 3)".to_string()));
+        assert_eq!(value("params"), Some("('T',)".to_string()));
+        match run_source("type Y = Missing\nY.__value__\n").1 {
+            Flow::Exception(exception) => assert_eq!(exception.to_string(), "NameError: name 'Missing' is not defined"),
+            flow => panic!("expected a NameError, got {:?}", flow),
+        }
+    }
+
+    #[test]
+    fn iter_with_a_sentinel_calls_any_callable() {
+        let source = "def counter():\n    n = 0\n    def step():\n        nonlocal n\n        n += 1\n        return n\n    return step\nitems = []\nfor item in iter(counter(), 4):\n    items += [item]\nempty = next(iter(lambda: 0, 0), 'done')\n";
+        let (interpreter, flow) = run_source(source);
+        assert_eq!(flow, Flow::Normal);
+        assert_eq!(global(&interpreter, "items").map(|value| value.repr()), Some("[1, 2, 3]".to_string()));
+        assert_eq!(global(&interpreter, "empty"), Some(Value::String("done".to_string())));
+        let error = |source| match run_source(source).1 {
+            Flow::Exception(exception) => exception.to_string(),
+            flow => panic!("expected an exception, got {:?}", flow),
+        };
+        // the builtin is called, it's not rejected as not callable
+        assert_eq!(error("next(iter(next, 0))\n"), "TypeError: next expected at least 1 argument, got 0");
+        assert_eq!(error("iter(1, 0)\n"), "TypeError: iter(v, w): v must be callable");
+    }
+
+    // the exception that stopped the source, as Python prints it
+    fn exception(source: &str) -> String {
+        match run_source(source).1 {
+            Flow::Exception(exception) => exception.to_string(),
+            flow => panic!("expected an exception, got {:?}", flow),
+        }
+    }
+
+    #[test]
+    fn call_arguments() {
+        let source = "def f(*args, **kwargs):\n    return args, kwargs\nr = f(0, *[1, 2], *(3,), **{'x': 1}, y=2)\ng = lambda a, b: a - b\ns = g(b=1, a=3)\n";
+        let (interpreter, flow) = run_source(source);
+        assert_eq!(flow, Flow::Normal);
+        assert_eq!(global(&interpreter, "r").map(|value| value.repr()), Some("((0, 1, 2, 3), {'x': 1, 'y': 2})".to_string()));
+        assert_eq!(global(&interpreter, "s"), Some(Value::Integer(2)));
+        let f = "def f(a):\n    return a\n";
+        assert_eq!(exception(&format!("{}f(1, 2)\n", f)), "TypeError: f() takes 1 positional argument but 2 were given");
+        assert_eq!(exception(&format!("{}f(1, a=2)\n", f)), "TypeError: f() got multiple values for argument 'a'");
+        assert_eq!(exception(&format!("{}f(b=1)\n", f)), "TypeError: f() got an unexpected keyword argument 'b'");
+        assert_eq!(exception("x = 1\nx()\n"), "TypeError: 'int' object is not callable");
+    }
+
+    #[test]
+    fn parameter_kinds_and_defaults() {
+        let source = "def f(a, /, b, *args, c, d=4, **kwargs):\n    return (a, b, args, c, d, kwargs)\nr1 = f(1, 2, 3, 4, c=5)\nr2 = f(1, b=2, c=3, e=6)\ndef g(a, b=[]):\n    b += [a]\n    return b\nr3 = [g(1), g(2)]\n";
+        let (interpreter, flow) = run_source(source);
+        assert_eq!(flow, Flow::Normal);
+        let value = |name| global(&interpreter, name).map(|value| value.repr());
+        assert_eq!(value("r1"), Some("(1, 2, (3, 4), 5, 4, {})".to_string()));
+        assert_eq!(value("r2"), Some("(1, 2, (), 3, 4, {'e': 6})".to_string()));
+        // defaults are copied into each call, lists are values here
+        assert_eq!(value("r3"), Some("[[1], [2]]".to_string()));
+        assert_eq!(exception("def f(a, /, b):\n    return a\nf(a=1, b=2)\n"), "TypeError: f() got some positional-only arguments passed as keyword arguments: 'a'");
+        assert_eq!(exception("def f(*, c):\n    return c\nf()\n"), "TypeError: f() missing 1 required keyword-only argument: 'c'");
+        // the defaults are evaluated when the def runs
+        assert_eq!(exception("def f(a=missing):\n    pass\n"), "NameError: name 'missing' is not defined");
+    }
+
+    #[test]
+    fn return_values() {
+        let source = "def f():\n    for i in range(10):\n        while True:\n            if i == 3:\n                return i\n            break\ndef g():\n    pass\nr = f()\ns = g()\n";
+        let (interpreter, flow) = run_source(source);
+        assert_eq!(flow, Flow::Normal);
+        // a return leaves the loops it's in, falling off the end returns None
+        assert_eq!(global(&interpreter, "r"), Some(Value::Integer(3)));
+        assert_eq!(global(&interpreter, "s"), Some(Value::None));
+        let (tokens, _) = tokenize_with("return 1\n", TokenizerOptions::default());
+        let statements = Parser::new(tokens).parse_tokens().unwrap();
+        assert_eq!(run(&statements).unwrap_err().to_string(), "SyntaxError: 'return' outside function");
+    }
+
+    #[test]
+    fn loop_control() {
+        let source = "found = None\nfor i in range(5):\n    if i % 2:\n        continue\n    if i == 4:\n        found = i\n        break\nelse:\n    found = -1\nn = 0\nwhile n < 3:\n    n += 1\nelse:\n    n = n * 10\nskipped = []\nfor x in []:\n    pass\nelse:\n    skipped += ['else']\n";
+        let (interpreter, flow) = run_source(source);
+        assert_eq!(flow, Flow::Normal);
+        // the else clause runs only when the loop ends without a break
+        assert_eq!(global(&interpreter, "found"), Some(Value::Integer(4)));
+        assert_eq!(global(&interpreter, "n"), Some(Value::Integer(30)));
+        assert_eq!(global(&interpreter, "skipped").map(|value| value.repr()), Some("['else']".to_string()));
+    }
+
+    #[test]
+    fn closures_global_and_nonlocal() {
+        let source = "def make():\n    n = 0\n    def inc():\n        nonlocal n\n        n += 1\n        return n\n    return inc\nc = make()\nc()\nr = c()\ntotal = 0\ndef add(x):\n    global total\n    total += x\nadd(2)\nadd(3)\nx = 'global'\ndef f():\n    x = 'local'\n    def g():\n        return x\n    return g()\ns = f()\nfs = []\nfor i in range(3):\n    fs += [lambda: i]\nlate = fs[0]()\n";
+        let (interpreter, flow) = run_source(source);
+        assert_eq!(flow, Flow::Normal);
+        let value = |name| global(&interpreter, name).map(|value| value.repr());
+        assert_eq!(value("r"), Some("2".to_string()));
+        assert_eq!(value("total"), Some("5".to_string()));
+        assert_eq!((value("s"), value("x")), (Some("'local'".to_string()), Some("'global'".to_string())));
+        // closures see the variable, not its value when they were made
+        assert_eq!(value("late"), Some("2".to_string()));
+        assert_eq!(exception("x = 1\ndef f():\n    y = x\n    x = 2\nf()\n"), "UnboundLocalError: cannot access local variable 'x' where it is not associated with a value");
+        assert_eq!(exception("def f():\n    nonlocal q\n"), "SyntaxError: no binding for nonlocal 'q' found");
+        assert_eq!(exception("def f():\n    global a\n    nonlocal a\n"), "SyntaxError: name 'a' is nonlocal and global");
+    }
+
+    #[test]
+    fn operator_precedence() {
+        let source = "a = 2 ** 3 ** 2\nb = -2 ** 2\nc = 1 + 2 * 3 - 4 // 3\nd = not 1 == 2 and 3 or 4\ne = 1 < 2 < 3 == 3\nf = 2 | 1 ^ 3 & 1 << 2\ng = 1 if 0 else 2 if 1 else 3\nh = (lambda x: x * 2)(4)\n";
+        let (interpreter, flow) = run_source(source);
+        assert_eq!(flow, Flow::Normal);
+        let values: Vec<String> = ["a", "b", "c", "d", "e", "f", "g", "h"].iter().map(|name| global(&interpreter, name).unwrap().repr()).collect();
+        // `**` is right associative and binds tighter than a unary minus on its left
+        assert_eq!(values, ["512", "-4", "6", "3", "True", "3", "2", "8"]);
+    }
+
+    #[test]
+    fn for_loops_over_iterables() {
+        let source = "out = []\nfor c in 'ab':\n    out += [c]\nfor k in {'x': 1, 'y': 2}:\n    out += [k]\nfor i in range(10, 0, -4):\n    out += [i]\nfor a, b in [(1, 2), [3, 4]]:\n    out += [a + b]\nfor e in {5}:\n    out += [e]\nit = iter([6, 7, 8])\nfirst = next(it)\nfor rest in it:\n    out += [rest]\n";
+        let (interpreter, flow) = run_source(source);
+        assert_eq!(flow, Flow::Normal);
+        assert_eq!(global(&interpreter, "out").map(|value| value.repr()), Some("['a', 'b', 'x', 'y', 10, 6, 2, 3, 7, 5, 7, 8]".to_string()));
+        assert_eq!(exception("for x in 1:\n    pass\n"), "TypeError: 'int' object is not iterable");
+        assert_eq!(exception("for a, b in [(1, 2, 3)]:\n    pass\n"), "ValueError: too many values to unpack (expected 2)");
     }
 }
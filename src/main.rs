@@ -1,36 +1,169 @@
+mod format;
 mod interpreter;
 mod tokenizer;
 mod parser;
+mod source;
+mod dump;
+mod diagnostic;
 
-use crate::tokenizer::Tokenizer;
+use std::env;
+use std::fs;
+use std::process;
+use std::thread;
+
+use crate::tokenizer::{tokenize_with, untokenize, ParseError, TokenizeError, TokenizeErrorKind, TokenizerOptions};
 use crate::parser::Parser;
+use crate::source::{decode_source, Source};
+use crate::dump::dump_tokens;
+use crate::diagnostic::Diagnostic;
+
+// Tokenize each decoded file keeping the trivia and check that untokenize gives back
+// the same text, like `cargo run -- --roundtrip script.py other.py`.
+fn roundtrip(paths: &[String]) -> bool {
+    let options = TokenizerOptions { keep_trivia: true, ..Default::default() };
+    let mut all_equal = true;
+    for path in paths {
+        let source = match read_source(path) {
+            Ok(source) => source.text,
+            Err(error) => {
+                eprintln!("{}: {}", path, error);
+                all_equal = false;
+                continue;
+            }
+        };
+        let (tokens, errors) = tokenize_with(&source, options);
+        if let Some(error) = errors.first() {
+            eprintln!("{}: {}", path, error);
+            all_equal = false;
+            continue;
+        }
+        let output = untokenize(&tokens);
+        if output == source {
+            println!("{}: ok", path);
+        } else {
+            let at = output.bytes().zip(source.bytes()).take_while(|(a, b)| a == b).count();
+            println!("{}: differs at byte {}", path, at);
+            all_equal = false;
+        }
+    }
+    all_equal
+}
+
+// read and decode a source file like Python does
+fn read_source(path: &str) -> Result<Source, String> {
+    let bytes = fs::read(path).map_err(|error| error.to_string())?;
+    decode_source(&bytes).map_err(|error| error.to_string())
+}
+
+// read and decode a source file, exiting when it can't be done
+fn load(path: &str) -> Source {
+    match read_source(path) {
+        Ok(source) => source,
+        Err(error) => {
+            eprintln!("{}: {}", path, error);
+            process::exit(1);
+        }
+    }
+}
+
+// Print the tokens like `python -m tokenize [-e] file.py`, so the output can be
+// compared with CPython: `cargo run -- --tokenize [-e] file.py`.
+fn tokenize_file(args: &[String]) {
+    let (exact, path) = match args {
+        [flag, path] if flag == "-e" || flag == "--exact" => (true, path),
+        [path] => (false, path),
+        _ => {
+            eprintln!("usage: --tokenize [-e] file.py");
+            process::exit(2);
+        }
+    };
+    let (output, error) = dump_tokens(&load(path), exact);
+    print!("{}", output);
+    if let Some(error) = error {
+        eprintln!("{}:{}:{}: error: {}", path, error.span.line, error.span.column - 1, error.message);
+        process::exit(1);
+    }
+}
+
+// A parse error caused by a tokenizer error: on the line of a character
+// that couldn't be read, or anywhere after a bracket that was never closed,
+// since the rest of the file is inside of it.
+fn follows_token_error(error: &ParseError, token_errors: &[TokenizeError]) -> bool {
+    let span = error.span();
+    token_errors.iter().any(|token_error| {
+        let start = token_error.span;
+        span.line == start.line || (token_error.kind == TokenizeErrorKind::UnclosedBracket && span.start >= start.start)
+    })
+}
 
-#[allow(warnings)]
+// Each Python call takes a few tens of KB of Rust stack in a debug build, so
+// the default 8MB would overflow before the interpreter's recursion limit.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
 
 fn main() {
+    let child = thread::Builder::new().stack_size(STACK_SIZE).spawn(run_main).expect("can't start the interpreter thread");
+    // a panic has already been reported by the thread
+    if child.join().is_err() {
+        process::exit(101);
+    }
+}
+
+fn run_main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("--roundtrip") => {
+            let all_equal = roundtrip(&args[1..]);
+            process::exit(if all_equal { 0 } else { 1 });
+        }
+        Some("--tokenize") => {
+            tokenize_file(&args[1..]);
+            return;
+        }
+        _ => {}
+    }
+
     // interpreter::main();
 
-    let source_code = "
+    // run the file given as argument, or the demo code
+    let file = args.first().map(String::as_str).unwrap_or("<demo>");
+    let source_code = match args.first() {
+        Some(path) => load(path).text,
+        None => "
 import os, sys
 import time
 def func(a=45,b=\"42\"):
     print(a)
-a = func";
-
-    let tokens = Tokenizer(source_code);
-    dbg!(&tokens);
+a = func".to_string(),
+    };
 
+    // every syntax error is reported, the tokenizer skips the text it can't
+    // read and the AST has Error nodes in place of the statements that failed
+    let options = TokenizerOptions { recover_errors: true, ..Default::default() };
+    let (tokens, token_errors) = tokenize_with(&source_code, options);
     let mut parser = Parser::new(tokens);
-    match parser.parse_tokens() {
-        Ok(ast) => {dbg!(ast);}
-        _ => {}
+    let (ast, errors) = parser.parse_recovering();
+    let mut diagnostics: Vec<Diagnostic> = token_errors.iter().map(Diagnostic::from).collect();
+    for error in errors.iter().filter(|error| !follows_token_error(error, &token_errors)) {
+        diagnostics.push(Diagnostic::from(error));
+    }
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span.map(|span| (span.line, span.column)));
+    for diagnostic in &diagnostics {
+        eprint!("{}", diagnostic.render(file, &source_code));
+    }
+
+    // like Python, nothing runs when the file has a syntax error
+    if !token_errors.is_empty() || !errors.is_empty() {
+        process::exit(1);
+    }
+    if let Err(exception) = interpreter::run(&ast) {
+        eprint!("{}", Diagnostic::from(&exception).render(file, &source_code));
+        process::exit(1);
     }
 }
 
 use std::sync::mpsc;
-use std::thread;
-use std::time::Duration;
 
+#[allow(dead_code)]
 fn main2() {
     // --snip--
 
@@ -70,3 +203,18 @@ fn main2() {
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrip_decodes_the_source() {
+        // latin-1 isn't valid UTF-8, and the line endings are normalized when decoding
+        let path = env::temp_dir().join(format!("roundtrip-{}.py", process::id()));
+        fs::write(&path, b"# -*- coding: latin-1 -*-\r\nname = 'caf\xe9'\r\n").unwrap();
+        let all_equal = roundtrip(&[path.to_string_lossy().into_owned()]);
+        fs::remove_file(&path).unwrap();
+        assert!(all_equal);
+    }
+}
@@ -1,314 +1,1689 @@
-use crate::interpreter::{Value, Expression, Statement, BinaryOperator, UnaryOperator, Function};
-use crate::tokenizer::{Token, ParseError, Symbol, Keyword};
+use crate::interpreter::{Value, Expression, Argument, BinaryOperator, UnaryOperator, Statement, StatementKind, Function, Parameter, ParameterKind, MatchCase, Pattern, Target, pattern_names};
+use crate::diagnostic::describe_token;
+use crate::tokenizer::{Token, SpannedToken, Span, ParseError, Symbol, Keyword, Number, FStringPart, SoftKeyword, parse_soft_keyword};
 
 impl Token {
     pub fn as_value(&self) -> Option<Value> {
         match self {
-            Token::FString(text) => { Some(Value::String(text.clone())) }
-            Token::Text(text) => {
-                if let Ok(integer) = text.parse::<i64>() {
-                    Some(Value::Integer(integer))
-                } else if let Ok(float) = text.parse::<f64>() {
-                    Some(Value::Float(float))
-                } else if let Ok(boolean) = text.parse::<bool>() {
-                    Some(Value::Boolean(boolean))
-                } else {
-                    Some(Value::String(text.clone()))
-                }
-            }
+            Token::String(text) => { Some(Value::String(text.clone())) }
+            Token::Bytes(bytes) => { Some(Value::Bytes(bytes.clone())) }
+            Token::Number(number) => match number {
+                Number::Integer(integer) => Some(Value::Integer(*integer)),
+                Number::LargeInteger(_) => None,
+                Number::Float(float) => Some(Value::Float(*float)),
+                Number::Imaginary(imaginary) => Some(Value::Complex(0.0, *imaginary)),
+            },
             _ => None,
         }
     }
 }
 
-// Design decisions: - Strings are consecutive chars and if we have symbols and chars into a String we 
-// can ignore then to build a single string, but the parser be wraped to this design decision. Using tokenizer 
-// as raw interpreter and so build a string from tokens validated gives more abstraction and flexibility 
-// to build the AST to syntax.
+// A pattern that matches any subject, described like in the error for the
+// patterns after it: `_` is a wildcard and a plain name a capture.
+fn irrefutable(pattern: &Pattern) -> Option<String> {
+    match pattern {
+        Pattern::Wildcard => Some("wildcard".to_string()),
+        Pattern::Capture(name) => Some(format!("name capture '{}'", name)),
+        Pattern::As(pattern, name) => irrefutable(pattern).map(|_| format!("name capture '{}'", name)),
+        // the alternatives before the last one were checked when they were parsed
+        Pattern::Or(alternatives) => alternatives.last().and_then(irrefutable),
+        _ => None,
+    }
+}
 
-#[derive(Debug)]
-enum SyntaxState{
-    IntoStatement,
-    IntoParenthesis, //into function call, assigment or expression
-    PrevIdentifier, // into assigment line, can destructure
-    IntoFunction, //inside scope function
-    IntoContainer, //list, tuple, ..
-    IntoAssigment, 
-    IntoImport,
-    // IntoIf, 
-    // IntoFor, 
-    // IntoWhile, 
-    // IntoExpression, 
-    EndStatement,
-    End,
+// Binding powers of the operators, from the loosest to the tightest. Comparisons
+// all share one level and chain, `**` is right associative.
+const OR: u8 = 1;
+const AND: u8 = 2;
+const NOT: u8 = 3;
+const COMPARE: u8 = 4;
+const BITWISE_OR: u8 = 5;
+const BITWISE_XOR: u8 = 6;
+const BITWISE_AND: u8 = 7;
+const SHIFT: u8 = 8;
+const SUM: u8 = 9;
+const TERM: u8 = 10;
+const UNARY: u8 = 11;
+const POWER: u8 = 12;
+
+// Nested expressions, targets and patterns the parser goes into before giving
+// up, so deep input is a syntax error instead of a stack overflow
+const MAX_DEPTH: usize = 200;
+
+// binary operator of the token and its binding power, comparisons aside
+fn binary_operator(token: &Token) -> Option<(BinaryOperator, u8)> {
+    let operator = match token {
+        Token::Keyword(Keyword::Or) => (BinaryOperator::Or, OR),
+        Token::Keyword(Keyword::And) => (BinaryOperator::And, AND),
+        Token::Symbol(Symbol::BitwiseOr) => (BinaryOperator::BitwiseOr, BITWISE_OR),
+        Token::Symbol(Symbol::BitwiseXor) => (BinaryOperator::BitwiseXor, BITWISE_XOR),
+        Token::Symbol(Symbol::BitwiseAnd) => (BinaryOperator::BitwiseAnd, BITWISE_AND),
+        Token::Symbol(Symbol::ShiftLeft) => (BinaryOperator::ShiftLeft, SHIFT),
+        Token::Symbol(Symbol::ShiftRight) => (BinaryOperator::ShiftRight, SHIFT),
+        Token::Symbol(Symbol::Plus) => (BinaryOperator::Add, SUM),
+        Token::Symbol(Symbol::Minus) => (BinaryOperator::Subtract, SUM),
+        Token::Symbol(Symbol::Multiply) => (BinaryOperator::Multiply, TERM),
+        Token::Symbol(Symbol::Divide) => (BinaryOperator::Divide, TERM),
+        Token::Symbol(Symbol::FloorDivide) => (BinaryOperator::FloorDivide, TERM),
+        Token::Symbol(Symbol::Modulo) => (BinaryOperator::Modulo, TERM),
+        Token::Symbol(Symbol::MatrixMultiply) => (BinaryOperator::MatrixMultiply, TERM),
+        Token::Symbol(Symbol::Exponent) => (BinaryOperator::Power, POWER),
+        _ => return None,
+    };
+    Some(operator)
 }
 
-#[derive(Debug)]
-enum FunctionState{
-    Begin,
-    IntoArguments, //list, tuple, ..
+// the operator of `+=`, `-=` and the other augmented assignments
+fn augmented_operator(token: &Token) -> Option<BinaryOperator> {
+    Some(match token {
+        Token::Symbol(Symbol::PlusAssign) => BinaryOperator::Add,
+        Token::Symbol(Symbol::MinusAssign) => BinaryOperator::Subtract,
+        Token::Symbol(Symbol::MultiplyAssign) => BinaryOperator::Multiply,
+        Token::Symbol(Symbol::DivideAssign) => BinaryOperator::Divide,
+        Token::Symbol(Symbol::FloorDivideAssign) => BinaryOperator::FloorDivide,
+        Token::Symbol(Symbol::ModuloAssign) => BinaryOperator::Modulo,
+        Token::Symbol(Symbol::ExponentAssign) => BinaryOperator::Power,
+        Token::Symbol(Symbol::MatrixMultiplyAssign) => BinaryOperator::MatrixMultiply,
+        Token::Symbol(Symbol::BitwiseAndAssign) => BinaryOperator::BitwiseAnd,
+        Token::Symbol(Symbol::BitwiseOrAssign) => BinaryOperator::BitwiseOr,
+        Token::Symbol(Symbol::BitwiseXorAssign) => BinaryOperator::BitwiseXor,
+        Token::Symbol(Symbol::ShiftLeftAssign) => BinaryOperator::ShiftLeft,
+        Token::Symbol(Symbol::ShiftRightAssign) => BinaryOperator::ShiftRight,
+        _ => return None,
+    })
+}
+
+// tokens an expression can start with
+fn starts_expression(token: &Token) -> bool {
+    matches!(
+        token,
+        Token::Identifier(_)
+            | Token::Number(_)
+            | Token::String(_)
+            | Token::Bytes(_)
+            | Token::FString(_)
+            | Token::Keyword(Keyword::None | Keyword::True | Keyword::False | Keyword::Not | Keyword::Lambda | Keyword::Await)
+            | Token::Symbol(Symbol::LeftParen | Symbol::LeftBracket | Symbol::Minus | Symbol::Plus | Symbol::BitwiseNot)
+    )
 }
 
+// Design decisions: - Strings are consecutive chars and if we have symbols and chars into a String we 
+// can ignore then to build a single string, but the parser be wraped to this design decision. Using tokenizer 
+// as raw interpreter and so build a string from tokens validated gives more abstraction and flexibility 
+// to build the AST to syntax.
+
 #[derive(Debug)]
 pub struct Parser {
-    // using drain to remove tokens allow functions to get all tokens and jump
-    current_ident: i32,
     current_token: usize,
-    tokens: Vec<Token>,
-    ast: Vec<Statement>,
-    state: SyntaxState,
+    tokens: Vec<SpannedToken>,
+    // keep going after a syntax error, collecting them
+    recover: bool,
+    errors: Vec<ParseError>,
+    // inside the body of a loop of the current function, where `break` and
+    // `continue` are allowed
+    in_loop: bool,
+    // inside the body of a def, where `nonlocal` is allowed
+    in_function: bool,
+    // how many of the recursive productions are being parsed
+    depth: usize,
 }
 
+// One method per production of the grammar, `parse_statement` for
+// `statement: compound_stmt | simple_stmts` and so on. Statement sequences
+// are parsed in loops, only nested blocks and expressions recurse.
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
+    pub fn new(tokens: Vec<SpannedToken>) -> Self {
         Parser {
-            current_ident: 0,
             current_token: 0,
-            tokens: tokens,
-            ast: Vec::new(),
-            state:SyntaxState::IntoStatement,
+            tokens,
+            recover: false,
+            errors: Vec::new(),
+            in_loop: false,
+            in_function: false,
+            depth: 0,
         }
     }
 
-    fn parse_token(&mut self) -> Result<(), ParseError> {
+    /// Parse all the tokens into the statements of a module.
+    pub fn parse_tokens(&mut self) -> Result<Vec<Statement>, ParseError> {
+        let mut statements = Vec::new();
+        while self.peek().is_some() {
+            statements.extend(self.parse_statement_or_recover()?);
+        }
+        Ok(statements)
+    }
 
-        // dbg!(&self);
-        let token = self.tokens.get(self.current_token).cloned().unwrap();
-        dbg!(&self.state);
-        dbg!(&token);
-        self.current_token +=1;
-        
-        match self.state {
-            SyntaxState::IntoImport => {
-                while let Some(token) = self.tokens.get(self.current_token) {
-                    if let Token::LineBreak = token {
-                        break;
-                    }
-                    match token {
-                        Token::Identifier(ident) => {
-                            dbg!(&ident);
-                            self.ast.push(Statement::Import(ident.to_string()))
-                        }
-                        // ignore if not identifier, comma are not identifier too
-                        _ => {}
-                    }
-                    self.current_token +=1;
-                }
-                self.state = SyntaxState::IntoStatement;
-                self.parse_token();
-            } 
-            SyntaxState::IntoStatement => {
-                match &token {
-                    Token::Keyword(keyword) => {
-                        match keyword {
-                            Keyword::Import => { 
-                                self.state = SyntaxState::IntoImport;
-                                self.parse_token();
-                            }
-                            Keyword::Def => {
-                                dbg!(keyword);
-                                self.state = SyntaxState::IntoFunction;
-                                self.parse_token();
-                            }
-                            Keyword::Return => {
-                                // let expr = Expression::Number(0); // placeholder expression
-                                // self.ast.push(AST::Expression(expr));
-                                self.state = SyntaxState::End;
-                            }
-                            _ => {
-                                return Err(ParseError::UnexpectedToken(token));
-                            }
-                        }
-                    },
+    /// Parse all the tokens, recovering from syntax errors at the end of the
+    /// statement that failed. The statement is a `StatementKind::Error` in the
+    /// AST and every error is returned, in the order they were found.
+    pub fn parse_recovering(&mut self) -> (Vec<Statement>, Vec<ParseError>) {
+        self.recover = true;
+        let statements = match self.parse_tokens() {
+            Ok(statements) => statements,
+            Err(error) => {
+                self.errors.push(error);
+                Vec::new()
+            }
+        };
+        (statements, std::mem::take(&mut self.errors))
+    }
+
+    fn parse_statement_or_recover(&mut self) -> Result<Vec<Statement>, ParseError> {
+        self.skip_spaces();
+        let start = self.current_token;
+        match self.parse_statement() {
+            Err(error) if self.recover => {
+                self.errors.push(error);
+                self.synchronize();
+                Ok(vec![Statement::new(StatementKind::Error, self.span_between(start, start + 1))])
+            }
+            result => result,
+        }
+    }
 
-                    Token::Space | Token::LineBreak => {
-                        self.parse_token();
+    // Skip what is left of a statement that failed: the rest of its line with
+    // the block following it, or up to the Dedent closing the current block.
+    fn synchronize(&mut self) {
+        let start = self.current_token;
+        while let Some(spanned) = self.tokens.get(self.current_token) {
+            match spanned.token {
+                Token::Dedent => break,
+                Token::LineBreak => {
+                    self.current_token += 1;
+                    if let Some(Token::Indent) = self.tokens.get(self.current_token).map(|spanned| &spanned.token) {
+                        self.skip_indented_block();
                     }
+                    break;
+                }
+                Token::Indent => {
+                    self.skip_indented_block();
+                    break;
+                }
+                _ => self.current_token += 1,
+            }
+        }
+        // a stray Dedent can't start a statement either
+        if self.current_token == start && start < self.tokens.len() {
+            self.current_token += 1;
+        }
+    }
+
+    // from an Indent to its matching Dedent, both included
+    fn skip_indented_block(&mut self) {
+        let mut depth = 0;
+        while let Some(spanned) = self.tokens.get(self.current_token) {
+            self.current_token += 1;
+            match spanned.token {
+                Token::Indent => depth += 1,
+                Token::Dedent if depth == 1 => break,
+                Token::Dedent => depth -= 1,
+                _ => {}
+            }
+        }
+    }
 
-                    Token::Identifier(ident) => {
-                        // dbg!(ident);
-                        self.state = SyntaxState::PrevIdentifier;
-                        // self.temp.push(token);
-                        // TODO: handle identifier
-                    },
-                    // Token::Symbol::LeftParen => {
-                    //     // TODO: handle function call / expression
-                    // },
-                    // Token::Symbol::LeftBracket | Token::Symbol::LeftParen | Token::Symbol::LeftCurlyBrace => {
-                    //     self.state = SyntaxState::IntoContainer;
-                    //     // TODO: handle container types (list, tuple, dict)
-                    // },
-                    _ => return Err(ParseError::UnexpectedToken(token)),
+    // statement: compound_stmt | simple_stmts
+    fn parse_statement(&mut self) -> Result<Vec<Statement>, ParseError> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(self.unexpected());
+        };
+        let start = self.current_token;
+        let statement = match token {
+            // an empty line
+            Token::LineBreak => {
+                self.current_token += 1;
+                return Ok(Vec::new());
+            }
+            // a line indented more than the one before it, outside of a block
+            Token::Indent => return Err(self.unexpected()),
+            Token::Keyword(Keyword::Def) => {
+                self.current_token += 1;
+                self.parse_function_def()?
+            }
+            Token::Keyword(Keyword::If) => {
+                self.current_token += 1;
+                self.parse_if()?
+            }
+            Token::Keyword(Keyword::While) => {
+                self.current_token += 1;
+                self.parse_while()?
+            }
+            Token::Keyword(Keyword::For) => {
+                self.current_token += 1;
+                self.parse_for()?
+            }
+            Token::Identifier(name) if parse_soft_keyword(&name) == Some(SoftKeyword::Match) && self.is_soft_keyword_statement(&name) => {
+                self.current_token += 1;
+                self.parse_match()?
+            }
+            _ => return self.parse_simple_statements(),
+        };
+        Ok(vec![Statement::new(statement, self.header_span(start))])
+    }
+
+    // simple_stmts: simple_stmt (';' simple_stmt)* [';'] NEWLINE
+    fn parse_simple_statements(&mut self) -> Result<Vec<Statement>, ParseError> {
+        let mut statements = self.parse_simple_statement()?;
+        while self.peek() == Some(&Token::Symbol(Symbol::SemiColon)) {
+            self.current_token += 1;
+            if matches!(self.peek(), Some(Token::LineBreak) | None) {
+                break;
+            }
+            statements.extend(self.parse_simple_statement()?);
+        }
+        self.end_of_line()?;
+        Ok(statements)
+    }
+
+    // simple_stmt: import, return, pass, break, continue, global, nonlocal, type
+    // alias, assignment to a name or an expression. `import a, b` gives a
+    // statement for each module.
+    fn parse_simple_statement(&mut self) -> Result<Vec<Statement>, ParseError> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(self.unexpected());
+        };
+        let start = self.current_token;
+        let statement = match token {
+            Token::Keyword(Keyword::Import) => {
+                self.current_token += 1;
+                let imports = self.parse_import()?;
+                let span = self.span_between(start, self.current_token);
+                return Ok(imports.into_iter().map(|import| Statement::new(import, span)).collect());
+            }
+            Token::Keyword(Keyword::Return) => {
+                self.current_token += 1;
+                self.parse_return()?
+            }
+            Token::Keyword(Keyword::Pass) => {
+                self.current_token += 1;
+                StatementKind::Pass
+            }
+            Token::Keyword(Keyword::Break) if !self.in_loop => {
+                return Err(self.invalid_syntax("'break' outside loop"));
+            }
+            Token::Keyword(Keyword::Break) => {
+                self.current_token += 1;
+                StatementKind::Break
+            }
+            Token::Keyword(Keyword::Continue) if !self.in_loop => {
+                return Err(self.invalid_syntax("'continue' not properly in loop"));
+            }
+            Token::Keyword(Keyword::Continue) => {
+                self.current_token += 1;
+                StatementKind::Continue
+            }
+            Token::Keyword(Keyword::Global) => {
+                self.current_token += 1;
+                StatementKind::Global(self.parse_names()?)
+            }
+            Token::Keyword(Keyword::Nonlocal) if !self.in_function => {
+                return Err(self.invalid_syntax("nonlocal declaration not allowed at module level"));
+            }
+            Token::Keyword(Keyword::Nonlocal) => {
+                self.current_token += 1;
+                StatementKind::Nonlocal(self.parse_names()?)
+            }
+            Token::Identifier(name) if parse_soft_keyword(&name) == Some(SoftKeyword::Type) && self.is_soft_keyword_statement(&name) => {
+                self.current_token += 1;
+                self.parse_type_alias()?
+            }
+            _ => self.parse_assignment_or_expression()?,
+        };
+        Ok(vec![Statement::new(statement, self.span_between(start, self.current_token))])
+    }
+
+    // assignment: (star_targets '=')+ expressions | target augassign expressions
+    // The targets are tried first, when they aren't followed by `=` or an
+    // augmented operator the statement is an expression.
+    fn parse_assignment_or_expression(&mut self) -> Result<StatementKind, ParseError> {
+        let start = self.current_token;
+        let assign = Token::Symbol(Symbol::Assign);
+        let target = match self.parse_target_list(&assign) {
+            Ok(target) if self.peek() == Some(&assign) => target,
+            Ok(target) => match self.peek().and_then(augmented_operator) {
+                Some(op) => {
+                    self.current_token += 1;
+                    if let Target::Sequence(_) | Target::Starred(_) = target {
+                        let message = "illegal expression for augmented assignment".to_string();
+                        return Err(ParseError::InvalidSyntax(message, self.tokens[start].span));
+                    }
+                    return Ok(StatementKind::AugmentedAssignment(target, op, self.parse_expression_list()?));
+                }
+                None => {
+                    self.current_token = start;
+                    return Ok(StatementKind::Expression(self.parse_expression_list()?));
                 }
             },
-            SyntaxState::IntoParenthesis => {
-                match token {
-                    Token::Identifier(name) => { },
-                    Token::Space => {
-                        self.parse_token();
+            Err(_) => {
+                self.current_token = start;
+                return Ok(StatementKind::Expression(self.parse_expression_list()?));
+            }
+        };
+        // `a = b = value` assigns the value to each target, left to right
+        let mut targets = vec![target];
+        loop {
+            self.current_token += 1;
+            let start = self.current_token;
+            match self.parse_target_list(&assign) {
+                Ok(target) if self.peek() == Some(&assign) => targets.push(target),
+                _ => {
+                    self.current_token = start;
+                    return Ok(StatementKind::Assignment(targets, self.parse_expression_list()?));
+                }
+            }
+        }
+    }
+
+    // import: 'import' dotted_name ['as' NAME] (',' dotted_name ['as' NAME])*
+    // The alias is not kept, importing has no effect yet.
+    fn parse_import(&mut self) -> Result<Vec<StatementKind>, ParseError> {
+        let mut statements = Vec::new();
+        loop {
+            let mut module = self.expect_identifier()?;
+            while self.peek() == Some(&Token::Symbol(Symbol::Dot)) {
+                self.current_token += 1;
+                module.push('.');
+                module.push_str(&self.expect_identifier()?);
+            }
+            if self.peek() == Some(&Token::Keyword(Keyword::As)) {
+                self.current_token += 1;
+                self.expect_identifier()?;
+            }
+            statements.push(StatementKind::Import(module));
+            if self.peek() != Some(&Token::Symbol(Symbol::Comma)) {
+                break;
+            }
+            self.current_token += 1;
+        }
+        Ok(statements)
+    }
+
+    // NAME (',' NAME)*, the names of a `global` or `nonlocal` declaration
+    fn parse_names(&mut self) -> Result<Vec<String>, ParseError> {
+        let mut names = vec![self.expect_identifier()?];
+        while self.peek() == Some(&Token::Symbol(Symbol::Comma)) {
+            self.current_token += 1;
+            names.push(self.expect_identifier()?);
+        }
+        Ok(names)
+    }
+
+    // `def name(params) -> annotation:` and its block, annotations are not kept
+    fn parse_function_def(&mut self) -> Result<StatementKind, ParseError> {
+        let name = self.expect_identifier()?;
+        self.expect(Token::Symbol(Symbol::LeftParen))?;
+        let params = self.parse_parameters(Symbol::RightParen)?;
+        self.expect(Token::Symbol(Symbol::RightParen))?;
+        if self.peek() == Some(&Token::Symbol(Symbol::Arrow)) {
+            self.current_token += 1;
+            self.parse_expression()?;
+        }
+        self.expect(Token::Symbol(Symbol::Colon))?;
+        // the loops around a def don't reach into its body
+        let in_loop = std::mem::replace(&mut self.in_loop, false);
+        let in_function = std::mem::replace(&mut self.in_function, true);
+        let body = self.parse_block();
+        self.in_loop = in_loop;
+        self.in_function = in_function;
+        Ok(StatementKind::Function(Function { name, params, body: body? }))
+    }
+
+    // Parameters up to the closing token, `)` for a def and `:` for a lambda.
+    // The rules of Python on where `/`, `*` and defaults can be are checked
+    // here, annotations are only allowed in a def and are not kept.
+    fn parse_parameters(&mut self, closing: Symbol) -> Result<Vec<Parameter>, ParseError> {
+        let closing = Token::Symbol(closing);
+        let annotations = closing == Token::Symbol(Symbol::RightParen);
+        let mut params: Vec<Parameter> = Vec::new();
+        let mut kind = ParameterKind::PositionalOrKeyword;
+        let mut slash = false;
+        let mut star = false;
+        // a bare `*` must be followed by a keyword-only parameter
+        let mut bare_star: Option<Span> = None;
+        let mut default_seen = false;
+        while self.peek() != Some(&closing) {
+            let span = self.tokens.get(self.current_token).map_or_else(|| self.end_span(), |spanned| spanned.span);
+            let invalid = |message: &str| Err(ParseError::InvalidSyntax(message.to_string(), span));
+            if params.last().is_some_and(|param| param.kind == ParameterKind::VarKeyword) {
+                return invalid("arguments cannot follow var-keyword argument");
+            }
+            let param = match self.peek() {
+                Some(Token::Symbol(Symbol::Divide)) => {
+                    self.current_token += 1;
+                    if slash {
+                        return invalid("/ may appear only once");
+                    } else if star {
+                        return invalid("/ must be ahead of *");
+                    } else if params.is_empty() {
+                        return invalid("at least one argument must precede /");
                     }
-                    Token::Symbol(symbol) => {
-                        match symbol {
-                            // assign default values into functions
-                            // destructure assigment if not in a function scope
-                            Symbol::Assign => {}
-                            // separate values, identifiers, literals, etc..
-                            Symbol::Comma => {}
-                            // ignore
-                            // string literal
-                            Symbol::DoubleQuote | Symbol::DoubleQuote => {}
-                            _ => todo!()
-                        }
+                    for param in &mut params {
+                        param.kind = ParameterKind::PositionalOnly;
                     }
-                    _ => { return Err(ParseError::UnexpectedToken(token)) },
+                    slash = true;
+                    None
                 }
-            },
-            SyntaxState::PrevIdentifier => {
-                match token {
-                    Token::Space => {
-                        self.parse_token();
+                Some(Token::Symbol(Symbol::Multiply)) => {
+                    self.current_token += 1;
+                    if star {
+                        return invalid("* argument may appear only once");
                     }
-                    Token::Symbol(s) => {
-                        match s{
-                            Symbol::Assign => {
-                                self.state = SyntaxState::IntoAssigment;
+                    star = true;
+                    kind = ParameterKind::KeywordOnly;
+                    match self.peek() {
+                        Some(Token::Identifier(_)) => {
+                            let name = self.expect_identifier()?;
+                            self.parse_annotation(annotations)?;
+                            if self.peek() == Some(&Token::Symbol(Symbol::Assign)) {
+                                return invalid("var-positional argument cannot have default value");
                             }
-                            _ => {} 
+                            Some(Parameter { name, kind: ParameterKind::VarPositional, default: None })
                         }
-                        // let targets = vec![]; // placeholder for assignment target(s)
-                        // self.ast.push(AST::Assignment { targets, value: None });
-                    },
-                    _ => return Err(ParseError::UnexpectedToken(token)),
+                        _ => {
+                            bare_star = Some(span);
+                            None
+                        }
+                    }
                 }
-            },
-            SyntaxState::IntoFunction => {
-                match token {
-                    // ignore
-                    Token::Space => {
-                        self.parse_token();
+                Some(Token::Symbol(Symbol::Exponent)) => {
+                    self.current_token += 1;
+                    let name = self.expect_identifier()?;
+                    self.parse_annotation(annotations)?;
+                    if self.peek() == Some(&Token::Symbol(Symbol::Assign)) {
+                        return invalid("var-keyword argument cannot have default value");
                     }
-                    // add to ast the signature of the function
-                    Token::Identifier(function_identifier) => {
-                        let mut fn_state = FunctionState::Begin;
-                        let mut fn_params : Vec<(String, Option<Value>)> = Vec::new();
-                        // let mut fn_statements : Vec<Statement> = Vec::new();
-
-                        while let Some(token) = self.tokens.get(self.current_token) {
-                            if let Token::Symbol(Symbol::Colon) = token {
-                                break;
-                            }
-                            match self.tokens.get(self.current_token).cloned().unwrap(){
-                                Token::Symbol(s) => {
-                                    match s {
-                                        Symbol::LeftParen => {
-                                            fn_state = FunctionState::IntoArguments;
-                                        }
-                                        // set default arguments
-                                        Symbol::Assign => {
-                                            self.current_token +=1;
-                                            let v = self.tokens.get(self.current_token).cloned().unwrap();
-                                            match &v {
-                                                Token::Text(t)|Token::FString(t) => {
-                                                    fn_params.last_mut().map(|params| params.1 = Some(v.as_value().unwrap()));
-                                                    dbg!(&fn_params);
-                                                }
-                                                _ => { return Err(ParseError::UnexpectedToken(v)); }
-                                            }
-                                        }
-                                        _ => { }
-                                    }
-                                }
-                                Token::Identifier(iden) => {
-                                    match fn_state {
-                                        FunctionState::IntoArguments => {
-                                            fn_params.push((iden, None));
-                                        }
-                                        _ => { }
-                                    }
-                                 }
-                                _ => { }
-                            }
-                            dbg!(&token);
-                            self.current_token +=1;
+                    Some(Parameter { name, kind: ParameterKind::VarKeyword, default: None })
+                }
+                _ => {
+                    let name = self.expect_identifier()?;
+                    self.parse_annotation(annotations)?;
+                    let default = match self.peek() {
+                        Some(Token::Symbol(Symbol::Assign)) => {
+                            self.current_token += 1;
+                            Some(self.parse_expression()?)
                         }
-                        // generate a function scope into ast
-                        let function = Function {
-                            name: function_identifier,
-                            params: fn_params,
-                            body: Vec::new(),
-                        };
-                        self.ast.push(Statement::Function(function));
-                        self.state = SyntaxState::IntoStatement;
-                        self.current_token +=1;
-                        self.parse_token();
-                        
+                        _ => None,
+                    };
+                    if kind == ParameterKind::PositionalOrKeyword {
+                        if default.is_none() && default_seen {
+                            return invalid("parameter without a default follows parameter with a default");
+                        }
+                        default_seen |= default.is_some();
                     }
-                    _ => return Err(ParseError::UnexpectedToken(token)),
+                    bare_star = None;
+                    Some(Parameter { name, kind, default })
+                }
+            };
+            if let Some(param) = param {
+                if params.iter().any(|other| other.name == param.name) {
+                    return invalid(&format!("duplicate argument '{}' in function definition", param.name));
+                }
+                params.push(param);
+            }
+            if self.peek() != Some(&Token::Symbol(Symbol::Comma)) {
+                break;
+            }
+            self.current_token += 1;
+        }
+        if let Some(span) = bare_star {
+            return Err(ParseError::InvalidSyntax("named arguments must follow bare *".to_string(), span));
+        }
+        Ok(params)
+    }
+
+    // `: annotation` after a parameter name
+    fn parse_annotation(&mut self, allowed: bool) -> Result<(), ParseError> {
+        if allowed && self.peek() == Some(&Token::Symbol(Symbol::Colon)) {
+            self.current_token += 1;
+            self.parse_expression()?;
+        }
+        Ok(())
+    }
+
+    // Adjacent string literals are joined into a single expression, like `"abc" 'def'`.
+    // Joining an f-string builds a JoinedStr, and bytes can't be mixed with str literals.
+    fn parse_string_literal(&mut self) -> Result<Expression, ParseError> {
+        let mut parts: Vec<Expression> = Vec::new();
+        let mut bytes: Option<Vec<u8>> = None;
+        let mut formatted = false;
+        let mut next = self.current_token;
+        while let Some(SpannedToken { token, span, .. }) = self.tokens.get(next).cloned() {
+            match token {
+                Token::Space => {
+                    next += 1;
+                    continue;
+                }
+                Token::Bytes(literal) if parts.is_empty() => {
+                    bytes.get_or_insert_with(Vec::new).extend(literal);
+                }
+                Token::String(text) if bytes.is_none() => {
+                    parts.push(Expression::Literal(Value::String(text)));
+                }
+                Token::FString(fstring) if bytes.is_none() => {
+                    formatted = true;
+                    parts.extend(Parser::parse_fstring(&fstring)?);
+                }
+                Token::String(_) | Token::Bytes(_) | Token::FString(_) => {
+                    return Err(ParseError::UnexpectedToken(token, span));
+                }
+                _ => break,
+            }
+            next += 1;
+            self.current_token = next;
+        }
+        if let Some(bytes) = bytes {
+            return Ok(Expression::Literal(Value::Bytes(bytes)));
+        }
+        // literal parts next to each other are merged into one string
+        let mut joined: Vec<Expression> = Vec::new();
+        for part in parts {
+            match (joined.last_mut(), part) {
+                (Some(Expression::Literal(Value::String(left))), Expression::Literal(Value::String(right))) => {
+                    left.push_str(&right);
                 }
+                (_, part) => joined.push(part),
+            }
+        }
+        match joined.pop() {
+            Some(Expression::Literal(value)) if !formatted && joined.is_empty() => Ok(Expression::Literal(value)),
+            Some(last) => {
+                joined.push(last);
+                Ok(Expression::JoinedStr(joined))
+            }
+            // an empty f-string
+            None if formatted => Ok(Expression::Literal(Value::String(String::new()))),
+            None => match self.tokens.get(self.current_token) {
+                Some(SpannedToken { token, span, .. }) => Err(ParseError::UnexpectedToken(token.clone(), *span)),
+                None => Err(ParseError::UnexpectedEndOfInput(self.end_span())),
             },
-            SyntaxState::IntoContainer => {
-                // TODO: handle container values (list items, tuple items, dict entries)
+        }
+    }
+
+    // Build the parts of a JoinedStr from the tokenized f-string. Each replacement
+    // field is parsed on its own from the tokens embedded into the f-string.
+    fn parse_fstring(fstring: &[FStringPart]) -> Result<Vec<Expression>, ParseError> {
+        let mut parts = Vec::new();
+        for part in fstring {
+            match part {
+                FStringPart::Literal(text) => parts.push(Expression::Literal(Value::String(text.clone()))),
+                FStringPart::Expression(field) => {
+                    if let Some(text) = &field.debug_text {
+                        parts.push(Expression::Literal(Value::String(text.clone())));
+                    }
+                    let mut parser = Parser::new(field.tokens.clone());
+                    let value = parser.parse_expression()?;
+                    parser.skip_spaces();
+                    if let Some(SpannedToken { token, span, .. }) = parser.tokens.get(parser.current_token) {
+                        return Err(ParseError::UnexpectedToken(token.clone(), *span));
+                    }
+                    let format_spec = match &field.format_spec {
+                        Some(spec) => Some(Box::new(Expression::JoinedStr(Parser::parse_fstring(spec)?))),
+                        None => None,
+                    };
+                    // `{value=}` shows the repr, unless a conversion or a format spec is given
+                    let conversion = match field.conversion {
+                        None if field.debug_text.is_some() && format_spec.is_none() => Some('r'),
+                        conversion => conversion,
+                    };
+                    parts.push(Expression::FormattedValue(Box::new(value), conversion, format_spec));
+                }
+            }
+        }
+        Ok(parts)
+    }
+
+    fn skip_spaces(&mut self) {
+        while let Some(SpannedToken { token: Token::Space, .. }) = self.tokens.get(self.current_token) {
+            self.current_token += 1;
+        }
+    }
+
+    // from the token at `start` to the last one before `end`, spaces left out
+    fn span_between(&self, start: usize, end: usize) -> Span {
+        let first = self.tokens[start].span;
+        let last = self.tokens[start..end].iter().rev().find(|spanned| spanned.token != Token::Space).map_or(first, |spanned| spanned.span);
+        Span { end: last.end, end_line: last.end_line, end_column: last.end_column, ..first }
+    }
+
+    // the place of a compound statement, the first line of its header
+    fn header_span(&self, start: usize) -> Span {
+        let end = self.tokens[start..].iter().position(|spanned| spanned.token == Token::LineBreak);
+        self.span_between(start, end.map_or(self.tokens.len(), |end| start + end))
+    }
+
+    // position right after the last token, used to report an unexpected end of input
+    fn end_span(&self) -> Span {
+        match self.tokens.last() {
+            Some(last) => Span {
+                start: last.span.end,
+                line: last.span.end_line,
+                column: last.span.end_column,
+                ..last.span
             },
-            SyntaxState::EndStatement => {
-                // not means end of program, but end of each statement
-
-                // add statement to a function scope if exist else add statement direct into global scope
-                let function_statements = if let Some(Statement::Function(function)) = self.ast.last_mut() {
-                    // Get the mutable reference to the Function statements Vec
-                    function 
-                } else {
-                    panic!("Expected Function statement in the ast vector");
+            None => Span::default(),
+        }
+    }
+
+    // `lambda params: body`, or a conditional expression `body if test else orelse`
+    fn parse_expression(&mut self) -> Result<Expression, ParseError> {
+        self.nested(Self::parse_conditional)
+    }
+
+    // expression: lambda | disjunction ['if' disjunction 'else' expression]
+    fn parse_conditional(&mut self) -> Result<Expression, ParseError> {
+        if self.peek() == Some(&Token::Keyword(Keyword::Lambda)) {
+            self.current_token += 1;
+            return self.parse_lambda();
+        }
+        let body = self.parse_binary(OR)?;
+        if self.peek() != Some(&Token::Keyword(Keyword::If)) {
+            return Ok(body);
+        }
+        self.current_token += 1;
+        let test = self.parse_binary(OR)?;
+        self.expect(Token::Keyword(Keyword::Else))?;
+        let orelse = self.parse_expression()?;
+        Ok(Expression::Conditional(Box::new(test), Box::new(body), Box::new(orelse)))
+    }
+
+    // Expressions separated by commas make a tuple without parentheses, like the
+    // right side of `a = 1, 2`.
+    fn parse_expression_list(&mut self) -> Result<Expression, ParseError> {
+        let first = self.parse_expression()?;
+        if self.peek() != Some(&Token::Symbol(Symbol::Comma)) {
+            return Ok(first);
+        }
+        let mut items = vec![first];
+        while self.peek() == Some(&Token::Symbol(Symbol::Comma)) {
+            self.current_token += 1;
+            match self.peek() {
+                Some(token) if starts_expression(token) => items.push(self.parse_expression()?),
+                _ => break,
+            }
+        }
+        Ok(Expression::Tuple(items))
+    }
+
+    fn parse_lambda(&mut self) -> Result<Expression, ParseError> {
+        let params = self.parse_parameters(Symbol::Colon)?;
+        self.expect(Token::Symbol(Symbol::Colon))?;
+        let body = self.parse_expression()?;
+        Ok(Expression::Lambda(params, Box::new(body)))
+    }
+
+    // Precedence climbing over the operators binding at least as tight as `min`.
+    // Prefix operators are only accepted where their precedence allows them, so
+    // `a + not b` is an error like in Python.
+    fn parse_binary(&mut self, min: u8) -> Result<Expression, ParseError> {
+        self.nested(|parser| parser.parse_operators(min))
+    }
+
+    fn parse_operators(&mut self, min: u8) -> Result<Expression, ParseError> {
+        let mut left = match self.peek() {
+            Some(Token::Keyword(Keyword::Not)) if min <= NOT => {
+                self.current_token += 1;
+                Expression::Unary(UnaryOperator::Not, Box::new(self.parse_binary(NOT)?))
+            }
+            Some(Token::Symbol(symbol @ (Symbol::Minus | Symbol::Plus | Symbol::BitwiseNot))) => {
+                let op = match symbol {
+                    Symbol::Minus => UnaryOperator::Minus,
+                    Symbol::Plus => UnaryOperator::Plus,
+                    _ => UnaryOperator::Invert,
                 };
-                function_statements.body.push(Statement::Expression(Expression::Literal(Value::Integer(6))));
-                dbg!(self.ast.last());
-            }
-            SyntaxState::End => {
-                return Ok(())
-                // return Err(ParseError::UnexpectedToken(token));
-            }
-            SyntaxState::IntoAssigment => {
-                match token {
-                    Token::Space => {}
-                    _ => {
-                        dbg!(&token);
-                        // let _id = match self.temp.pop() {
-                        //     Some(name) => match name {
-                        //         // Token::Identifier(n) => { 
-                        //         //     dbg!("------------------");
-                        //         //     dbg!(&n);
-                        //         //     n }
-                        //         _ => { "No name".to_string() }
-                        //     }
-                        //     _ => { "No name".to_string() }
-                        //     // _ => return Err(ParseError::UnexpectedToken(token)),
-                        // };
-                        // let _st = Statement::Assignment(_id,Expression::Literal(Value::Integer(3)));
-                        // self.ast.push(_st);
-                        self.state = SyntaxState::End;
+                self.current_token += 1;
+                Expression::Unary(op, Box::new(self.parse_binary(UNARY)?))
+            }
+            _ => self.parse_await()?,
+        };
+        loop {
+            if COMPARE >= min {
+                if let Some(op) = self.comparison_operator() {
+                    // the whole chain `a < b < c` is one node
+                    let mut comparisons = vec![(op, self.parse_binary(COMPARE + 1)?)];
+                    while let Some(op) = self.comparison_operator() {
+                        comparisons.push((op, self.parse_binary(COMPARE + 1)?));
                     }
+                    left = Expression::Compare(Box::new(left), comparisons);
+                    continue;
                 }
             }
+            let Some((op, power)) = self.peek().and_then(binary_operator) else {
+                break;
+            };
+            if power < min {
+                break;
+            }
+            self.current_token += 1;
+            // `**` is right associative and its right operand can be negative, `2 ** -1`
+            let right = match op {
+                BinaryOperator::Power => self.parse_binary(UNARY)?,
+                _ => self.parse_binary(power + 1)?,
+            };
+            left = Expression::Binary(Box::new(left), op, Box::new(right));
         }
+        Ok(left)
+    }
 
-        Ok(())
+    // Read the comparison operator at the current token, the two word ones
+    // `not in` and `is not` included.
+    fn comparison_operator(&mut self) -> Option<BinaryOperator> {
+        let (op, len) = match self.peek()? {
+            Token::Symbol(Symbol::Equal) => (BinaryOperator::Equal, 1),
+            Token::Symbol(Symbol::NotEqual) => (BinaryOperator::NotEqual, 1),
+            Token::Symbol(Symbol::LessThan) => (BinaryOperator::LessThan, 1),
+            Token::Symbol(Symbol::GreaterThan) => (BinaryOperator::GreaterThan, 1),
+            Token::Symbol(Symbol::LessThanOrEqual) => (BinaryOperator::LessThanOrEqual, 1),
+            Token::Symbol(Symbol::GreaterThanOrEqual) => (BinaryOperator::GreaterThanOrEqual, 1),
+            Token::Keyword(Keyword::In) => (BinaryOperator::In, 1),
+            Token::Keyword(Keyword::Not) => match self.peek_nth(1) {
+                Some(Token::Keyword(Keyword::In)) => (BinaryOperator::NotIn, 2),
+                _ => return None,
+            },
+            Token::Keyword(Keyword::Is) => match self.peek_nth(1) {
+                Some(Token::Keyword(Keyword::Not)) => (BinaryOperator::IsNot, 2),
+                _ => (BinaryOperator::Is, 1),
+            },
+            _ => return None,
+        };
+        for _ in 0..len {
+            self.peek();
+            self.current_token += 1;
+        }
+        Some(op)
+    }
+
+    // `await` binds tighter than any operator, `await x ** 2` is `(await x) ** 2`
+    fn parse_await(&mut self) -> Result<Expression, ParseError> {
+        if self.peek() != Some(&Token::Keyword(Keyword::Await)) {
+            return self.parse_primary();
+        }
+        self.current_token += 1;
+        Ok(Expression::Await(Box::new(self.parse_primary()?)))
+    }
+
+    // an atom followed by calls, attributes and subscripts, `a.b(c)[d]`
+    fn parse_primary(&mut self) -> Result<Expression, ParseError> {
+        let mut primary = self.parse_atom()?;
+        loop {
+            primary = match self.peek() {
+                Some(Token::Symbol(Symbol::LeftParen)) => {
+                    self.current_token += 1;
+                    Expression::FunctionCall(Box::new(primary), self.parse_arguments()?)
+                }
+                Some(Token::Symbol(Symbol::Dot)) => {
+                    self.current_token += 1;
+                    Expression::Attribute(Box::new(primary), self.expect_identifier()?)
+                }
+                Some(Token::Symbol(Symbol::LeftBracket)) => {
+                    self.current_token += 1;
+                    let index = self.parse_expression_list()?;
+                    self.expect(Token::Symbol(Symbol::RightBracket))?;
+                    Expression::Subscript(Box::new(primary), Box::new(index))
+                }
+                _ => return Ok(primary),
+            };
+        }
     }
 
-    fn parse_expression(&mut self) -> Result<(), ParseError> {
-        // Implement expression parsing logic here
+    // Arguments of a call up to the closing parenthesis. Like in Python the
+    // positional ones can't follow a keyword or a `**mapping`, and a keyword
+    // can't be given twice.
+    fn parse_arguments(&mut self) -> Result<Vec<Argument>, ParseError> {
+        let mut args = Vec::new();
+        let mut keywords: Vec<String> = Vec::new();
+        let mut mapping = false;
+        while self.peek() != Some(&Token::Symbol(Symbol::RightParen)) {
+            let span = self.tokens.get(self.current_token).map_or_else(|| self.end_span(), |spanned| spanned.span);
+            let arg = match (self.peek().cloned(), self.peek_nth(1)) {
+                (Some(Token::Symbol(Symbol::Multiply)), _) => {
+                    self.current_token += 1;
+                    Argument::Unpack(self.parse_expression()?)
+                }
+                (Some(Token::Symbol(Symbol::Exponent)), _) => {
+                    self.current_token += 1;
+                    mapping = true;
+                    Argument::UnpackMapping(self.parse_expression()?)
+                }
+                (Some(Token::Identifier(name)), Some(Token::Symbol(Symbol::Assign))) => {
+                    self.current_token += 1;
+                    self.expect(Token::Symbol(Symbol::Assign))?;
+                    if keywords.contains(&name) {
+                        return Err(ParseError::InvalidSyntax(format!("keyword argument repeated: {}", name), span));
+                    }
+                    keywords.push(name.clone());
+                    Argument::Keyword(name, self.parse_expression()?)
+                }
+                _ if mapping => {
+                    return Err(ParseError::InvalidSyntax("positional argument follows keyword argument unpacking".to_string(), span));
+                }
+                _ if !keywords.is_empty() => {
+                    return Err(ParseError::InvalidSyntax("positional argument follows keyword argument".to_string(), span));
+                }
+                _ => Argument::Positional(self.parse_expression()?),
+            };
+            args.push(arg);
+            if self.peek() != Some(&Token::Symbol(Symbol::Comma)) {
+                break;
+            }
+            self.current_token += 1;
+        }
+        self.expect(Token::Symbol(Symbol::RightParen))?;
+        Ok(args)
+    }
+
+    // names, constants, literals and bracketed expressions
+    fn parse_atom(&mut self) -> Result<Expression, ParseError> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(self.expected("an expression"));
+        };
+        let expression = match &token {
+            Token::String(_) | Token::Bytes(_) | Token::FString(_) => return self.parse_string_literal(),
+            Token::Identifier(name) => Expression::Variable(name.clone()),
+            Token::Keyword(Keyword::None) => Expression::Literal(Value::None),
+            Token::Keyword(Keyword::True) => Expression::Literal(Value::Boolean(true)),
+            Token::Keyword(Keyword::False) => Expression::Literal(Value::Boolean(false)),
+            Token::Number(Number::LargeInteger(literal)) => Expression::LargeInteger(literal.clone()),
+            Token::Number(_) => Expression::Literal(token.as_value().unwrap()),
+            // `(x)` is only a group, `()` and `(x,)` are tuples
+            Token::Symbol(Symbol::LeftParen) => {
+                self.current_token += 1;
+                let (mut items, trailing_comma) = self.parse_items(Symbol::RightParen)?;
+                if items.len() == 1 && !trailing_comma {
+                    return Ok(items.pop().unwrap());
+                }
+                return Ok(Expression::Tuple(items));
+            }
+            Token::Symbol(Symbol::LeftBracket) => {
+                self.current_token += 1;
+                return Ok(Expression::List(self.parse_items(Symbol::RightBracket)?.0));
+            }
+            Token::Symbol(Symbol::LeftCurlyBrace) => {
+                self.current_token += 1;
+                return self.parse_dict_or_set();
+            }
+            _ => return Err(self.expected("an expression")),
+        };
+        self.current_token += 1;
+        Ok(expression)
+    }
+
+    // `{}` and `{key: value, ...}` are dicts, `{item, ...}` is a set
+    fn parse_dict_or_set(&mut self) -> Result<Expression, ParseError> {
+        let closing = Token::Symbol(Symbol::RightCurlyBrace);
+        if self.peek() == Some(&closing) {
+            self.current_token += 1;
+            return Ok(Expression::Dict(Vec::new()));
+        }
+        let first = self.parse_expression()?;
+        if self.peek() != Some(&Token::Symbol(Symbol::Colon)) {
+            let mut items = vec![first];
+            if self.peek() == Some(&Token::Symbol(Symbol::Comma)) {
+                self.current_token += 1;
+                items.extend(self.parse_items(Symbol::RightCurlyBrace)?.0);
+            } else {
+                self.expect(closing)?;
+            }
+            return Ok(Expression::Set(items));
+        }
+        let mut items = Vec::new();
+        let mut key = first;
+        loop {
+            self.expect(Token::Symbol(Symbol::Colon))?;
+            items.push((key, self.parse_expression()?));
+            if self.peek() != Some(&Token::Symbol(Symbol::Comma)) {
+                break;
+            }
+            self.current_token += 1;
+            if self.peek() == Some(&closing) {
+                break;
+            }
+            key = self.parse_expression()?;
+        }
+        self.expect(closing)?;
+        Ok(Expression::Dict(items))
+    }
+
+    // expressions separated by commas up to the closing bracket, and whether
+    // the last one had a trailing comma
+    fn parse_items(&mut self, closing: Symbol) -> Result<(Vec<Expression>, bool), ParseError> {
+        let closing = Token::Symbol(closing);
+        let mut items = Vec::new();
+        let mut trailing_comma = false;
+        while self.peek() != Some(&closing) {
+            items.push(self.parse_expression()?);
+            trailing_comma = self.peek() == Some(&Token::Symbol(Symbol::Comma));
+            if !trailing_comma {
+                break;
+            }
+            self.current_token += 1;
+        }
+        self.expect(closing)?;
+        Ok((items, trailing_comma))
+    }
+
+    // a simple statement ends with the line, or with the input
+    fn end_of_line(&mut self) -> Result<(), ParseError> {
+        match self.peek() {
+            Some(Token::LineBreak) => self.current_token += 1,
+            None => {}
+            Some(_) => return Err(self.expected("end of line")),
+        }
         Ok(())
     }
 
-    fn parse_block(&mut self) -> Result<(), ParseError> {
-        // Implement block parsing logic here
+    // `return` with an optional value, `None` when it's missing
+    fn parse_return(&mut self) -> Result<StatementKind, ParseError> {
+        let value = match self.peek() {
+            Some(token) if starts_expression(token) => self.parse_expression_list()?,
+            _ => Expression::Literal(Value::None),
+        };
+        Ok(StatementKind::Return(value))
+    }
+
+    // `if test:` and its block, an `elif` chain is nested into the else blocks
+    fn parse_if(&mut self) -> Result<StatementKind, ParseError> {
+        let test = self.parse_expression()?;
+        self.expect(Token::Symbol(Symbol::Colon))?;
+        let body = self.parse_block()?;
+        // the `elif` branches are read in a loop, a long chain doesn't recurse
+        let mut branches = Vec::new();
+        while self.peek() == Some(&Token::Keyword(Keyword::Elif)) {
+            let start = self.current_token;
+            self.current_token += 1;
+            let test = self.parse_expression()?;
+            self.expect(Token::Symbol(Symbol::Colon))?;
+            branches.push((start, test, self.parse_block()?));
+        }
+        let mut orelse = match self.peek() {
+            Some(Token::Keyword(Keyword::Else)) => {
+                self.current_token += 1;
+                self.expect(Token::Symbol(Symbol::Colon))?;
+                Some(self.parse_block()?)
+            }
+            _ => None,
+        };
+        // each `elif` is an If alone in the else block of the one before
+        while let Some((start, test, body)) = branches.pop() {
+            orelse = Some(vec![Statement::new(StatementKind::If(test, body, orelse), self.header_span(start))]);
+        }
+        Ok(StatementKind::If(test, body, orelse))
+    }
+
+    fn parse_while(&mut self) -> Result<StatementKind, ParseError> {
+        let test = self.parse_expression()?;
+        self.expect(Token::Symbol(Symbol::Colon))?;
+        let body = self.parse_loop_body()?;
+        Ok(StatementKind::While(test, body, self.parse_loop_else()?))
+    }
+
+    // the block of a loop, where `break` and `continue` are allowed
+    fn parse_loop_body(&mut self) -> Result<Vec<Statement>, ParseError> {
+        let in_loop = std::mem::replace(&mut self.in_loop, true);
+        let body = self.parse_block();
+        self.in_loop = in_loop;
+        body
+    }
+
+    // `else:` block of a loop, run when the loop ends without a `break`. A
+    // `break` in it belongs to an enclosing loop.
+    fn parse_loop_else(&mut self) -> Result<Option<Vec<Statement>>, ParseError> {
+        if self.peek() != Some(&Token::Keyword(Keyword::Else)) {
+            return Ok(None);
+        }
+        self.current_token += 1;
+        self.expect(Token::Symbol(Symbol::Colon))?;
+        Ok(Some(self.parse_block()?))
+    }
+
+    // `for target in iterable:` and its block, with an optional `else:` block
+    fn parse_for(&mut self) -> Result<StatementKind, ParseError> {
+        let target = self.parse_target_list(&Token::Keyword(Keyword::In))?;
+        self.expect(Token::Keyword(Keyword::In))?;
+        let iterable = self.parse_expression_list()?;
+        self.expect(Token::Symbol(Symbol::Colon))?;
+        let body = self.parse_loop_body()?;
+        Ok(StatementKind::For(target, iterable, body, self.parse_loop_else()?))
+    }
+
+    // star_targets: a single target, or targets separated by commas that
+    // unpack the value, like `a, (b, *rest)`. Stops before the closing token.
+    fn parse_target_list(&mut self, closing: &Token) -> Result<Target, ParseError> {
+        self.peek();
+        let span = self.tokens.get(self.current_token).map_or_else(|| self.end_span(), |spanned| spanned.span);
+        let (mut targets, trailing_comma) = self.parse_targets(closing)?;
+        if targets.len() > 1 || trailing_comma {
+            return Ok(Target::Sequence(targets));
+        }
+        match targets.pop() {
+            Some(Target::Starred(_)) => {
+                Err(ParseError::InvalidSyntax("starred assignment target must be in a list or tuple".to_string(), span))
+            }
+            Some(target) => Ok(target),
+            None => unreachable!("a target list has at least one target"),
+        }
+    }
+
+    // targets separated by commas up to the closing token, and whether they
+    // end with a comma
+    fn parse_targets(&mut self, closing: &Token) -> Result<(Vec<Target>, bool), ParseError> {
+        let mut targets = Vec::new();
+        let mut star = false;
+        loop {
+            if self.peek() == Some(&Token::Symbol(Symbol::Multiply)) {
+                if star {
+                    return Err(self.invalid_syntax("multiple starred expressions in assignment"));
+                }
+                star = true;
+            }
+            targets.push(self.parse_target()?);
+            if self.peek() != Some(&Token::Symbol(Symbol::Comma)) {
+                return Ok((targets, false));
+            }
+            self.current_token += 1;
+            if self.peek() == Some(closing) {
+                return Ok((targets, true));
+            }
+        }
+    }
+
+    // target: NAME | primary '.' NAME | primary '[' expressions ']' | '*' target
+    //       | '(' star_targets ')' | '[' star_targets ']'
+    fn parse_target(&mut self) -> Result<Target, ParseError> {
+        self.nested(Self::parse_single_target)
+    }
+
+    fn parse_single_target(&mut self) -> Result<Target, ParseError> {
+        let closing = match self.peek() {
+            // `name`, `object.name` or `object[index]`
+            Some(Token::Identifier(_)) => {
+                let span = self.tokens[self.current_token].span;
+                return match self.parse_primary()? {
+                    Expression::Variable(name) => Ok(Target::Name(name)),
+                    Expression::Attribute(object, name) => Ok(Target::Attribute(*object, name)),
+                    Expression::Subscript(object, index) => Ok(Target::Subscript(*object, *index)),
+                    _ => Err(ParseError::InvalidSyntax("cannot assign to function call".to_string(), span)),
+                };
+            }
+            Some(Token::Symbol(Symbol::Multiply)) => {
+                self.current_token += 1;
+                // `* *rest` has nothing to unpack into
+                if self.peek() == Some(&Token::Symbol(Symbol::Multiply)) {
+                    return Err(self.invalid_syntax("cannot use starred expression here"));
+                }
+                return Ok(Target::Starred(Box::new(self.parse_target()?)));
+            }
+            Some(Token::Symbol(Symbol::LeftParen)) => Token::Symbol(Symbol::RightParen),
+            Some(Token::Symbol(Symbol::LeftBracket)) => Token::Symbol(Symbol::RightBracket),
+            _ => return Err(self.expected("a name")),
+        };
+        self.current_token += 1;
+        // `()` and `[]` unpack an empty iterable
+        if self.peek() == Some(&closing) {
+            self.current_token += 1;
+            return Ok(Target::Sequence(Vec::new()));
+        }
+        // `(a)` is just `a`, but `[a]` and `[*rest]` unpack
+        let target = match closing {
+            Token::Symbol(Symbol::RightBracket) => Target::Sequence(self.parse_targets(&closing)?.0),
+            _ => self.parse_target_list(&closing)?,
+        };
+        self.expect(closing)?;
+        Ok(target)
+    }
+
+    // block: simple_stmts on the line of the `:`, or NEWLINE INDENT statement+ DEDENT
+    fn parse_block(&mut self) -> Result<Vec<Statement>, ParseError> {
+        self.nested(Self::parse_block_body)
+    }
+
+    fn parse_block_body(&mut self) -> Result<Vec<Statement>, ParseError> {
+        if self.peek() != Some(&Token::LineBreak) {
+            return self.parse_simple_statements();
+        }
+        self.current_token += 1;
+        self.expect(Token::Indent)?;
+        let mut statements = Vec::new();
+        loop {
+            match self.peek() {
+                Some(Token::Dedent) => {
+                    self.current_token += 1;
+                    break;
+                }
+                None => return Err(self.unexpected()),
+                Some(_) => statements.extend(self.parse_statement_or_recover()?),
+            }
+        }
+        Ok(statements)
+    }
+
+    // Run a recursive production one level deeper, failing past MAX_DEPTH
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, ParseError>) -> Result<T, ParseError> {
+        if self.depth >= MAX_DEPTH {
+            self.peek();
+            let span = self.tokens.get(self.current_token).map_or_else(|| self.end_span(), |spanned| spanned.span);
+            return Err(ParseError::RecursionLimitExceeded(span));
+        }
+        self.depth += 1;
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    // next token after the spaces, without consuming it
+    fn peek(&mut self) -> Option<&Token> {
+        self.skip_spaces();
+        self.tokens.get(self.current_token).map(|spanned| &spanned.token)
+    }
+
+    // the nth token after the current one, not counting spaces
+    fn peek_nth(&self, n: usize) -> Option<&Token> {
+        self.tokens[self.current_token.min(self.tokens.len())..]
+            .iter()
+            .map(|spanned| &spanned.token)
+            .filter(|token| **token != Token::Space)
+            .nth(n)
+    }
+
+    fn unexpected(&self) -> ParseError {
+        match self.tokens.get(self.current_token) {
+            Some(SpannedToken { token, span, .. }) => ParseError::UnexpectedToken(token.clone(), *span),
+            None => ParseError::UnexpectedEndOfInput(self.end_span()),
+        }
+    }
+
+    // error for a token that is missing, described like "':'" or "a name"
+    fn expected(&self, expected: &str) -> ParseError {
+        match self.tokens.get(self.current_token) {
+            Some(SpannedToken { token, span, .. }) => ParseError::ExpectedToken(expected.to_string(), token.clone(), *span),
+            None => ParseError::UnexpectedEndOfInput(self.end_span()),
+        }
+    }
+
+    // error for valid tokens in a place where Python doesn't allow them
+    fn invalid_syntax(&self, message: &str) -> ParseError {
+        match self.tokens.get(self.current_token) {
+            Some(spanned) => ParseError::InvalidSyntax(message.to_string(), spanned.span),
+            None => ParseError::UnexpectedEndOfInput(self.end_span()),
+        }
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<(), ParseError> {
+        if self.peek() != Some(&expected) {
+            return Err(self.expected(&describe_token(&expected)));
+        }
+        self.current_token += 1;
         Ok(())
     }
 
-    pub fn parse_tokens(&mut self) -> Result<Vec<Statement>, ParseError> {
-        // dbg!(&tokens);
-        self.parse_token()?;
-        Ok(self.ast.clone())
+    fn expect_identifier(&mut self) -> Result<String, ParseError> {
+        match self.peek().cloned() {
+            Some(Token::Identifier(name)) => {
+                self.current_token += 1;
+                Ok(name)
+            }
+            _ => Err(self.expected("a name")),
+        }
+    }
+
+    // The soft keyword at the current token starts a statement when `match` ends
+    // its line with `:` and opens a block, or `type` is followed by a name and
+    // `=` or `[`. Otherwise it's a plain name, like in `match = re.match(text)`.
+    fn is_soft_keyword_statement(&self, name: &str) -> bool {
+        match parse_soft_keyword(name) {
+            Some(SoftKeyword::Match) => {
+                // scan only up to the end of the line, not the rest of the file
+                let mut tokens = self.tokens[self.current_token + 1..]
+                    .iter()
+                    .map(|spanned| &spanned.token)
+                    .filter(|token| **token != Token::Space);
+                let mut last = match tokens.next() {
+                    None | Some(Token::LineBreak | Token::Symbol(Symbol::Colon)) => return false,
+                    Some(token) => token,
+                };
+                for token in tokens.by_ref() {
+                    if *token == Token::LineBreak {
+                        return *last == Token::Symbol(Symbol::Colon) && tokens.next() == Some(&Token::Indent);
+                    }
+                    last = token;
+                }
+                false
+            }
+            Some(SoftKeyword::Type) => matches!(
+                (self.peek_nth(1), self.peek_nth(2)),
+                (Some(Token::Identifier(_)), Some(Token::Symbol(Symbol::Assign | Symbol::LeftBracket)))
+            ),
+            _ => false,
+        }
+    }
+
+    // `match subject:` followed by an indented block of `case pattern [if guard]:` blocks
+    fn parse_match(&mut self) -> Result<StatementKind, ParseError> {
+        let subject = self.parse_expression_list()?;
+        self.expect(Token::Symbol(Symbol::Colon))?;
+        self.expect(Token::LineBreak)?;
+        self.expect(Token::Indent)?;
+        let mut cases = Vec::new();
+        // a case that matches anything can only be the last one
+        let mut unreachable = None;
+        loop {
+            match self.peek() {
+                Some(Token::Dedent) => {
+                    self.current_token += 1;
+                    break;
+                }
+                Some(Token::Identifier(name)) if parse_soft_keyword(name) == Some(SoftKeyword::Case) => {
+                    if let Some(error) = unreachable.take() {
+                        if !self.recover {
+                            return Err(error);
+                        }
+                        self.errors.push(error);
+                    }
+                    self.current_token += 1;
+                    // a case that fails is left out, the next ones are still parsed
+                    match self.parse_case() {
+                        Ok((case, pattern_span)) => {
+                            if let (None, Some(what)) = (&case.guard, irrefutable(&case.pattern)) {
+                                let message = format!("{} makes remaining patterns unreachable", what);
+                                unreachable = Some(ParseError::InvalidSyntax(message, pattern_span));
+                            }
+                            cases.push(case);
+                        }
+                        Err(error) if self.recover => {
+                            self.errors.push(error);
+                            self.synchronize();
+                        }
+                        Err(error) => return Err(error),
+                    }
+                }
+                _ => return Err(self.unexpected()),
+            }
+        }
+        Ok(StatementKind::Match(subject, cases))
+    }
+
+    // the case and the place of its pattern
+    fn parse_case(&mut self) -> Result<(MatchCase, Span), ParseError> {
+        self.skip_spaces();
+        let start = self.current_token;
+        let pattern = self.parse_pattern()?;
+        let pattern_span = self.span_between(start, self.current_token);
+        let guard = match self.peek() {
+            Some(Token::Keyword(Keyword::If)) => {
+                self.current_token += 1;
+                Some(self.parse_expression()?)
+            }
+            _ => None,
+        };
+        self.expect(Token::Symbol(Symbol::Colon))?;
+        let body = self.parse_block()?;
+        Ok((MatchCase { pattern, guard, body }, pattern_span))
+    }
+
+    // Top level pattern of a case, `case a, *rest:` is a sequence without brackets.
+    fn parse_pattern(&mut self) -> Result<Pattern, ParseError> {
+        let first = self.parse_sequence_item()?;
+        if self.peek() != Some(&Token::Symbol(Symbol::Comma)) {
+            if let Pattern::Star(_) = first {
+                return Err(self.unexpected());
+            }
+            return Ok(first);
+        }
+        let mut items = vec![first];
+        while self.peek() == Some(&Token::Symbol(Symbol::Comma)) {
+            self.current_token += 1;
+            // trailing comma
+            if let Some(Token::Symbol(Symbol::Colon) | Token::Keyword(Keyword::If)) = self.peek() {
+                break;
+            }
+            items.push(self.parse_sequence_item()?);
+        }
+        Ok(Pattern::Sequence(items))
+    }
+
+    fn parse_sequence_item(&mut self) -> Result<Pattern, ParseError> {
+        if self.peek() != Some(&Token::Symbol(Symbol::Multiply)) {
+            return self.parse_as_pattern();
+        }
+        self.current_token += 1;
+        let name = self.expect_identifier()?;
+        match parse_soft_keyword(&name) {
+            Some(SoftKeyword::Underscore) => Ok(Pattern::Star(None)),
+            _ => Ok(Pattern::Star(Some(name))),
+        }
+    }
+
+    // `pattern as name`
+    fn parse_as_pattern(&mut self) -> Result<Pattern, ParseError> {
+        let pattern = self.parse_or_pattern()?;
+        if self.peek() != Some(&Token::Keyword(Keyword::As)) {
+            return Ok(pattern);
+        }
+        self.current_token += 1;
+        let span = self.tokens.get(self.current_token).map(|spanned| spanned.span);
+        let name = self.expect_identifier()?;
+        if parse_soft_keyword(&name) == Some(SoftKeyword::Underscore) {
+            return Err(ParseError::UnexpectedToken(Token::Identifier(name), span.unwrap_or_default()));
+        }
+        Ok(Pattern::As(Box::new(pattern), name))
+    }
+
+    // alternatives separated by `|`
+    fn parse_or_pattern(&mut self) -> Result<Pattern, ParseError> {
+        self.skip_spaces();
+        let start = self.current_token;
+        let mut alternatives = vec![self.parse_closed_pattern()?];
+        let mut alternative_start = start;
+        while self.peek() == Some(&Token::Symbol(Symbol::BitwiseOr)) {
+            // like the cases of a match, only the last alternative can match anything
+            if let Some(what) = irrefutable(alternatives.last().unwrap()) {
+                let message = format!("{} makes remaining patterns unreachable", what);
+                return Err(ParseError::InvalidSyntax(message, self.span_between(alternative_start, self.current_token)));
+            }
+            self.current_token += 1;
+            self.skip_spaces();
+            alternative_start = self.current_token;
+            alternatives.push(self.parse_closed_pattern()?);
+        }
+        if alternatives.len() == 1 {
+            return Ok(alternatives.pop().unwrap());
+        }
+        // every alternative must bind the same names, whichever one matches
+        let names = |pattern: &Pattern| {
+            let mut names = Vec::new();
+            pattern_names(pattern, &mut names);
+            names.sort();
+            names
+        };
+        if alternatives.iter().any(|alternative| names(alternative) != names(&alternatives[0])) {
+            return Err(ParseError::InvalidSyntax("alternative patterns bind different names".to_string(), self.span_between(start, self.current_token)));
+        }
+        Ok(Pattern::Or(alternatives))
+    }
+
+    fn parse_closed_pattern(&mut self) -> Result<Pattern, ParseError> {
+        self.nested(Self::parse_closed_pattern_body)
+    }
+
+    fn parse_closed_pattern_body(&mut self) -> Result<Pattern, ParseError> {
+        let Some(token) = self.peek().cloned() else {
+            return Err(self.unexpected());
+        };
+        match token {
+            Token::Identifier(name) => {
+                self.current_token += 1;
+                // `Color.RED` is compared to the subject, a plain name captures it
+                let mut value = None;
+                while self.peek() == Some(&Token::Symbol(Symbol::Dot)) {
+                    self.current_token += 1;
+                    let object = value.unwrap_or_else(|| Expression::Variable(name.clone()));
+                    value = Some(Expression::Attribute(Box::new(object), self.expect_identifier()?));
+                }
+                // there are no classes to match against
+                if self.peek() == Some(&Token::Symbol(Symbol::LeftParen)) {
+                    return Err(self.invalid_syntax("class patterns are not supported"));
+                }
+                match (value, parse_soft_keyword(&name)) {
+                    (Some(value), _) => Ok(Pattern::Value(value)),
+                    (None, Some(SoftKeyword::Underscore)) => Ok(Pattern::Wildcard),
+                    (None, _) => Ok(Pattern::Capture(name)),
+                }
+            }
+            Token::Number(_) | Token::Keyword(Keyword::None | Keyword::True | Keyword::False) => {
+                match self.parse_atom()? {
+                    Expression::Literal(value) => Ok(Pattern::Literal(value)),
+                    large @ Expression::LargeInteger(_) => Ok(Pattern::Value(large)),
+                    _ => Err(self.unexpected()),
+                }
+            }
+            // only numbers can be negative
+            Token::Symbol(Symbol::Minus) => {
+                self.current_token += 1;
+                match self.peek().cloned() {
+                    Some(Token::Number(number)) => {
+                        self.current_token += 1;
+                        Ok(Pattern::Literal(match number {
+                            Number::LargeInteger(literal) => {
+                                return Ok(Pattern::Value(Expression::Unary(UnaryOperator::Minus, Box::new(Expression::LargeInteger(literal)))));
+                            }
+                            Number::Integer(integer) => Value::Integer(-integer),
+                            Number::Float(float) => Value::Float(-float),
+                            Number::Imaginary(imaginary) => Value::Complex(-0.0, -imaginary),
+                        }))
+                    }
+                    _ => Err(self.unexpected()),
+                }
+            }
+            Token::String(_) | Token::Bytes(_) => match self.parse_string_literal()? {
+                Expression::Literal(value) => Ok(Pattern::Literal(value)),
+                _ => Err(self.unexpected()),
+            },
+            // `(pattern)` groups, `()` and `(pattern,)` are sequences
+            Token::Symbol(Symbol::LeftParen) => {
+                self.current_token += 1;
+                let (items, trailing_comma) = self.parse_pattern_items(Symbol::RightParen)?;
+                match (items.len(), trailing_comma) {
+                    (1, false) if !matches!(items[0], Pattern::Star(_)) => Ok(items.into_iter().next().unwrap()),
+                    _ => Ok(Pattern::Sequence(items)),
+                }
+            }
+            Token::Symbol(Symbol::LeftBracket) => {
+                self.current_token += 1;
+                let (items, _) = self.parse_pattern_items(Symbol::RightBracket)?;
+                Ok(Pattern::Sequence(items))
+            }
+            Token::Symbol(Symbol::LeftCurlyBrace) => {
+                self.current_token += 1;
+                self.parse_mapping_pattern()
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    // `{key: pattern, ..., **rest}` after the `{`, the rest comes last
+    fn parse_mapping_pattern(&mut self) -> Result<Pattern, ParseError> {
+        let closing = Token::Symbol(Symbol::RightCurlyBrace);
+        let mut items = Vec::new();
+        let mut rest = None;
+        while self.peek() != Some(&closing) {
+            if self.peek() == Some(&Token::Symbol(Symbol::Exponent)) {
+                self.current_token += 1;
+                let span = self.tokens.get(self.current_token).map(|spanned| spanned.span);
+                let name = self.expect_identifier()?;
+                if parse_soft_keyword(&name) == Some(SoftKeyword::Underscore) {
+                    return Err(ParseError::UnexpectedToken(Token::Identifier(name), span.unwrap_or_default()));
+                }
+                rest = Some(name);
+                if self.peek() == Some(&Token::Symbol(Symbol::Comma)) {
+                    self.current_token += 1;
+                }
+                break;
+            }
+            let is_key = match self.peek() {
+                Some(Token::Identifier(_)) => self.peek_nth(1) == Some(&Token::Symbol(Symbol::Dot)),
+                Some(token) => matches!(
+                    token,
+                    Token::Number(_)
+                        | Token::String(_)
+                        | Token::Bytes(_)
+                        | Token::Symbol(Symbol::Minus)
+                        | Token::Keyword(Keyword::None | Keyword::True | Keyword::False)
+                ),
+                None => false,
+            };
+            if !is_key {
+                return Err(self.invalid_syntax("mapping pattern keys may only match literals and attribute lookups"));
+            }
+            let key = self.parse_closed_pattern()?;
+            self.expect(Token::Symbol(Symbol::Colon))?;
+            items.push((key, self.parse_as_pattern()?));
+            if self.peek() != Some(&Token::Symbol(Symbol::Comma)) {
+                break;
+            }
+            self.current_token += 1;
+        }
+        self.expect(closing)?;
+        Ok(Pattern::Mapping(items, rest))
+    }
+
+    // items of a bracketed sequence pattern, up to the closing bracket
+    fn parse_pattern_items(&mut self, closing: Symbol) -> Result<(Vec<Pattern>, bool), ParseError> {
+        let closing = Token::Symbol(closing);
+        let mut items = Vec::new();
+        let mut trailing_comma = false;
+        while self.peek() != Some(&closing) {
+            items.push(self.parse_sequence_item()?);
+            trailing_comma = self.peek() == Some(&Token::Symbol(Symbol::Comma));
+            if !trailing_comma {
+                break;
+            }
+            self.current_token += 1;
+        }
+        self.expect(closing)?;
+        Ok((items, trailing_comma))
+    }
+
+    // `type Name[T, U] = value`, the parameters are plain names
+    fn parse_type_alias(&mut self) -> Result<StatementKind, ParseError> {
+        let name = self.expect_identifier()?;
+        let mut params = Vec::new();
+        if self.peek() == Some(&Token::Symbol(Symbol::LeftBracket)) {
+            self.current_token += 1;
+            loop {
+                params.push(self.expect_identifier()?);
+                if self.peek() != Some(&Token::Symbol(Symbol::Comma)) {
+                    break;
+                }
+                self.current_token += 1;
+                if self.peek() == Some(&Token::Symbol(Symbol::RightBracket)) {
+                    break;
+                }
+            }
+            self.expect(Token::Symbol(Symbol::RightBracket))?;
+        }
+        self.expect(Token::Symbol(Symbol::Assign))?;
+        let value = self.parse_expression()?;
+        Ok(StatementKind::TypeAlias(name, params, value))
     }
 
 }
 
-// IntoStatement
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenizer::{tokenize_with, TokenizerOptions};
+
+    fn parse(source: &str) -> Result<Vec<Statement>, ParseError> {
+        let (tokens, errors) = tokenize_with(source, TokenizerOptions::default());
+        assert!(errors.is_empty(), "{:?}", errors);
+        Parser::new(tokens).parse_tokens()
+    }
+
+    // the message of the syntax error the source gives
+    fn syntax_error(source: &str) -> String {
+        match parse(source) {
+            Err(ParseError::InvalidSyntax(message, _)) => message,
+            other => panic!("expected an invalid syntax error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn starred_target_in_starred_target() {
+        assert_eq!(syntax_error("for a, * *b in [[1, 2]]: pass\n"), "cannot use starred expression here");
+        assert!(parse("for a, *b in [[1, 2]]: pass\n").is_ok());
+    }
+
+    #[test]
+    fn elif_chain_nests_in_else_blocks() {
+        let statements = parse("if a:\n    pass\nelif b:\n    pass\nelif c:\n    pass\nelse:\n    x = 1\n").unwrap();
+        let StatementKind::If(_, _, Some(orelse)) = &statements[0].kind else { panic!("not an if") };
+        let StatementKind::If(Expression::Variable(name), _, Some(orelse)) = &orelse[0].kind else { panic!("no elif") };
+        assert_eq!(name, "b");
+        assert_eq!(orelse[0].span.map(|span| span.line), Some(5));
+        let StatementKind::If(Expression::Variable(name), _, Some(orelse)) = &orelse[0].kind else { panic!("no second elif") };
+        assert_eq!(name, "c");
+        assert!(matches!(orelse[0].kind, StatementKind::Assignment(..)));
+    }
+
+    // run a test on a thread with the stack of a main thread, the test
+    // threads get less than the recursion limit needs in debug builds
+    fn with_main_stack<T: Send + 'static>(test: impl FnOnce() -> T + Send + 'static) -> T {
+        std::thread::Builder::new().stack_size(8 << 20).spawn(test).unwrap().join().unwrap()
+    }
+
+    #[test]
+    fn deeply_nested_blocks() {
+        let nested = |depth: usize| (0..depth).map(|level| format!("{}if x:\n", "    ".repeat(level))).collect::<String>() + &"    ".repeat(depth) + "pass\n";
+        assert!(parse(&nested(50)).is_ok());
+        let result = with_main_stack(move || parse(&nested(3000)).map(|_| ()));
+        assert!(matches!(result, Err(ParseError::RecursionLimitExceeded(_))));
+    }
+
+    #[test]
+    fn assignment_statements() {
+        let statements = parse("a, b = 1, 2\nx = y = f(z=1)\nobj.attr = v\nitems[0] += 1\nf(x)\nx == 1\n").unwrap();
+        assert!(matches!(&statements[0].kind, StatementKind::Assignment(targets, _) if matches!(targets[..], [Target::Sequence(_)])));
+        assert!(matches!(&statements[1].kind, StatementKind::Assignment(targets, Expression::FunctionCall(..)) if targets.len() == 2));
+        assert!(matches!(&statements[2].kind, StatementKind::Assignment(targets, _) if matches!(targets[..], [Target::Attribute(..)])));
+        assert!(matches!(&statements[3].kind, StatementKind::AugmentedAssignment(Target::Subscript(..), BinaryOperator::Add, _)));
+        assert!(matches!(&statements[4].kind, StatementKind::Expression(Expression::FunctionCall(..))));
+        assert!(matches!(&statements[5].kind, StatementKind::Expression(Expression::Compare(..))));
+        assert_eq!(syntax_error("a, b += 1\n"), "illegal expression for augmented assignment");
+    }
+
+    #[test]
+    fn unreachable_and_inconsistent_patterns() {
+        let cases = |cases: &str| format!("match x:\n{}", cases.lines().map(|line| format!("    {}\n        pass\n", line)).collect::<String>());
+        assert!(parse(&cases("case 1 | 2:\ncase y if y:\ncase [a, *b] | (b, a, *_):\ncase _:")).is_ok());
+        assert_eq!(syntax_error(&cases("case y:\ncase 1:")), "name capture 'y' makes remaining patterns unreachable");
+        assert_eq!(syntax_error(&cases("case (1 | _) as z:\ncase 1:")), "name capture 'z' makes remaining patterns unreachable");
+        assert_eq!(syntax_error(&cases("case 1 | _ | 2:")), "wildcard makes remaining patterns unreachable");
+        assert_eq!(syntax_error(&cases("case [a, b] | [a]:")), "alternative patterns bind different names");
+        assert_eq!(syntax_error(&cases("case {'k': a} | 1:")), "alternative patterns bind different names");
+    }
+
+    #[test]
+    fn recovers_at_the_next_statement() {
+        let source = "x = = 1\ny = 2\ndef f(a b):\n    pass\nwhile x\n    z = 3\nw = 4\n";
+        let (tokens, _) = tokenize_with(source, TokenizerOptions::default());
+        let (statements, errors) = Parser::new(tokens).parse_recovering();
+        let lines: Vec<usize> = errors.iter().map(|error| error.span().line).collect();
+        assert_eq!(lines, [1, 3, 5]);
+        // each failed statement is an Error node, the body of a failed header is skipped
+        let kinds: Vec<&str> = statements.iter().map(|statement| match &statement.kind {
+            StatementKind::Error => "error",
+            StatementKind::Assignment(..) => "assignment",
+            _ => "other",
+        }).collect();
+        assert_eq!(kinds, ["error", "assignment", "error", "error", "assignment"]);
+        assert_eq!(statements[2].span.map(|span| span.line), Some(3));
+    }
+
+    #[test]
+    fn break_and_continue_outside_loops() {
+        assert!(parse("while x:\n    if y:\n        break\n    continue\n").is_ok());
+        assert_eq!(syntax_error("def f():\n    break\n"), "'break' outside loop");
+        // a function body is not in the loop around the def
+        assert_eq!(syntax_error("while True:\n    def f():\n        continue\n"), "'continue' not properly in loop");
+    }
+
+    #[test]
+    fn soft_keywords_are_names_elsewhere() {
+        let source = "match = 1\ncase = [match]\ntype = 2\n_ = match + type\nmatch case:\n    case [1]:\n        _ = 5\n";
+        let statements = parse(source).unwrap();
+        assert!(matches!(&statements[0].kind, StatementKind::Assignment(targets, _) if matches!(&targets[..], [Target::Name(name)] if name == "match")));
+        assert!(matches!(&statements[4].kind, StatementKind::Match(Expression::Variable(name), cases) if name == "case" && cases.len() == 1));
+        assert!(matches!(&parse("type X[T] = list[T]\n").unwrap()[0].kind, StatementKind::TypeAlias(name, params, _) if name == "X" && params == &["T"]));
+        assert!(matches!(&parse("match(x)\n").unwrap()[0].kind, StatementKind::Expression(Expression::FunctionCall(..))));
+    }
+
+    #[test]
+    fn indented_blocks() {
+        let source = "def f(x):\n    while x:\n        for i in x:\n            pass\n    else:\n        return 1\nif a: b = 1; c = 2\n";
+        let statements = parse(source).unwrap();
+        let StatementKind::Function(function) = &statements[0].kind else { panic!("not a def") };
+        assert!(matches!(&function.body[..], [Statement { kind: StatementKind::While(_, body, Some(orelse)), .. }] if body.len() == 1 && orelse.len() == 1));
+        // a block can be the simple statements on the rest of the header line
+        assert!(matches!(&statements[1].kind, StatementKind::If(_, body, None) if body.len() == 2));
+        assert!(matches!(parse("if x:\npass\n"), Err(ParseError::ExpectedToken(expected, Token::Keyword(Keyword::Pass), _)) if expected == "indent"));
+    }
+}
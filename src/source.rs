@@ -0,0 +1,150 @@
+use std::fmt;
+
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+// Encodings a source file can declare with a `# -*- coding: name -*-` comment
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Encoding {
+    Utf8,
+    Latin1,
+    Ascii,
+}
+
+impl Encoding {
+    // name of the encoding the way Python reports it
+    pub fn name(&self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Latin1 => "iso-8859-1",
+            Encoding::Ascii => "ascii",
+        }
+    }
+}
+
+/// Problem found while turning the bytes of a file into source text.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SourceError {
+    // the name given in the coding declaration, and its line
+    UnknownEncoding(String, usize),
+    // a file starting with a UTF-8 BOM can't declare another encoding
+    EncodingWithBom(String),
+    // byte offset and line of a byte that is invalid in the encoding
+    InvalidByte { encoding: Encoding, byte: u8, position: usize, line: usize },
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SourceError::UnknownEncoding(name, line) => write!(f, "unknown encoding: {} at line {}", name, line),
+            SourceError::EncodingWithBom(name) => write!(f, "encoding problem: {} with BOM", name),
+            SourceError::InvalidByte { encoding, byte, position, line } => write!(
+                f,
+                "'{}' codec can't decode byte 0x{:02x} in position {} at line {}",
+                encoding.name(), byte, position, line
+            ),
+        }
+    }
+}
+
+/// Decoded source ready for the tokenizer, with `\n` line endings.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Source {
+    pub text: String,
+    pub encoding: Encoding,
+}
+
+// Name given by a PEP 263 declaration like `# -*- coding: latin-1 -*-` or
+// `# vim: set fileencoding=utf-8 :`. It only counts in a comment line.
+fn coding_declaration(line: &[u8]) -> Option<String> {
+    let line = String::from_utf8_lossy(line);
+    let comment = line.trim_start_matches([' ', '\t', '\x0c']).strip_prefix('#')?;
+    let start = comment.find("coding")? + "coding".len();
+    let rest = comment[start..].strip_prefix([':', '='])?;
+    let rest = rest.trim_start_matches([' ', '\t']);
+    let len = rest
+        .find(|ch: char| !(ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_' | '.')))
+        .unwrap_or(rest.len());
+    if len == 0 {
+        return None;
+    }
+    Some(rest[..len].to_string())
+}
+
+// Python accepts many spellings for the same encoding, like `UTF8` or `latin_1`.
+fn parse_encoding(name: &str) -> Option<Encoding> {
+    let name = name.to_lowercase().replace('_', "-");
+    let matches = |normal: &str| name == normal || name.starts_with(&format!("{}-", normal));
+    if matches("utf-8") || name == "utf8" {
+        Some(Encoding::Utf8)
+    } else if matches("latin-1") || matches("iso-8859-1") || matches("iso-latin-1") || name == "latin1" {
+        Some(Encoding::Latin1)
+    } else if name == "ascii" || name == "us-ascii" {
+        Some(Encoding::Ascii)
+    } else {
+        None
+    }
+}
+
+// the first two lines of the input, where a coding declaration can be.
+// Blank lines are kept, so the index of a line gives its number.
+fn first_lines(bytes: &[u8]) -> Vec<&[u8]> {
+    bytes.split(|byte| *byte == b'\n').map(|line| line.strip_suffix(b"\r").unwrap_or(line)).take(2).collect()
+}
+
+fn line_of(bytes: &[u8], position: usize) -> usize {
+    bytes[..position].iter().filter(|byte| **byte == b'\n').count() + 1
+}
+
+// `\r\n` and lone `\r` become `\n`, so the tokenizer only sees one line ending
+fn normalize_newlines(text: &str) -> String {
+    if !text.contains('\r') {
+        return text.to_string();
+    }
+    text.replace("\r\n", "\n").replace('\r', "\n")
+}
+
+/// Decode the bytes of a source file like Python does: a UTF-8 BOM or a coding
+/// declaration in the first two lines choose the encoding, UTF-8 otherwise.
+pub fn decode_source(bytes: &[u8]) -> Result<Source, SourceError> {
+    let (bytes, bom) = match bytes.strip_prefix(UTF8_BOM) {
+        Some(rest) => (rest, true),
+        None => (bytes, false),
+    };
+
+    let mut encoding = Encoding::Utf8;
+    for (index, line) in first_lines(bytes).into_iter().enumerate() {
+        if let Some(name) = coding_declaration(line) {
+            encoding = parse_encoding(&name).ok_or(SourceError::UnknownEncoding(name.clone(), index + 1))?;
+            if bom && encoding != Encoding::Utf8 {
+                return Err(SourceError::EncodingWithBom(name));
+            }
+            break;
+        }
+        // the second line only counts when the first one is blank or a comment
+        let first = String::from_utf8_lossy(line);
+        let first = first.trim_start_matches([' ', '\t', '\x0c']);
+        if !(first.is_empty() || first.starts_with('#')) {
+            break;
+        }
+    }
+
+    let invalid = |position: usize| SourceError::InvalidByte {
+        encoding,
+        byte: bytes[position],
+        position,
+        line: line_of(bytes, position),
+    };
+    let text = match encoding {
+        Encoding::Utf8 => match std::str::from_utf8(bytes) {
+            Ok(text) => text.to_string(),
+            Err(error) => return Err(invalid(error.valid_up_to())),
+        },
+        // every byte is the code point with the same value
+        Encoding::Latin1 => bytes.iter().map(|byte| *byte as char).collect(),
+        Encoding::Ascii => match bytes.iter().position(|byte| !byte.is_ascii()) {
+            Some(position) => return Err(invalid(position)),
+            None => String::from_utf8_lossy(bytes).into_owned(),
+        },
+    };
+    Ok(Source { text: normalize_newlines(&text), encoding })
+}
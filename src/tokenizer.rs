@@ -1,19 +1,138 @@
+use std::collections::VecDeque;
 use std::fmt;
-use regex::Regex;
+
+use unicode_ident::{is_xid_continue, is_xid_start};
+use unicode_normalization::UnicodeNormalization;
+
+use crate::format::float_repr;
+use crate::interpreter::Value;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Token {
     Identifier(String),
     Keyword(Keyword),
     Symbol(Symbol),
-    Text(String),
-    FString(String),
+    Number(Number),
+    String(String),
+    Bytes(Vec<u8>),
+    FString(Vec<FStringPart>),
+    // `# text` up to the end of the line, only kept when asked by the options
+    Comment(String),
+    // end of a logical line, blank lines don't generate it
     LineBreak,
+    // trivia kept with `keep_trivia`: line breaks that don't end a logical
+    // line (blank lines, comment lines, inside brackets) and backslash
+    // continuations
+    SoftLineBreak,
+    LineContinuation,
+    // the indentation level grows or shrinks at the start of a logical line
+    Indent,
+    Dedent,
     Space,
-    IncrementIdent,
     CarriageReturn,
-    Slash,
-    IdentationTab,
+}
+
+/// A piece of a formatted string literal, either plain text or a replacement
+/// field like `{value!r:>10}`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FStringPart {
+    Literal(String),
+    Expression(FStringExpression),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FStringExpression {
+    // tokens of the embedded expression, spans point into the outer source
+    pub tokens: Vec<SpannedToken>,
+    // source of `{value=}` fields up to the `=`, printed before the value
+    pub debug_text: Option<String>,
+    // `!s`, `!r` or `!a`
+    pub conversion: Option<char>,
+    // the format spec can contain nested replacement fields too
+    pub format_spec: Option<Vec<FStringPart>>,
+}
+
+/// Location of a token into the source code. `start` and `end` are byte offsets
+/// (end exclusive), lines and columns start at 1 and columns count chars.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SpannedToken {
+    pub token: Token,
+    pub span: Span,
+    // source text of the token, only kept with `keep_trivia`
+    pub raw: Option<String>,
+}
+
+// position of the tokenizer while the input is consumed
+#[derive(Clone, Copy, Debug)]
+struct Cursor {
+    offset: usize,
+    line: usize,
+    column: usize,
+}
+
+impl Cursor {
+    fn new() -> Self {
+        Cursor { offset: 0, line: 1, column: 1 }
+    }
+
+    // move the cursor over the consumed text and return the span it covers
+    fn advance(&mut self, consumed: &str) -> Span {
+        let (start, line, column) = (self.offset, self.line, self.column);
+        for ch in consumed.chars() {
+            if ch == '\n' {
+                self.line += 1;
+                self.column = 1;
+            } else {
+                self.column += 1;
+            }
+        }
+        self.offset += consumed.len();
+        Span {
+            start,
+            end: self.offset,
+            line,
+            column,
+            end_line: self.line,
+            end_column: self.column,
+        }
+    }
+
+    fn span(&self) -> Span {
+        Span {
+            start: self.offset,
+            end: self.offset,
+            line: self.line,
+            column: self.column,
+            end_line: self.line,
+            end_column: self.column,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Number {
+    Integer(i64),
+    // an integer that doesn't fit in an i64, kept as written in the source
+    LargeInteger(String),
+    Float(f64),
+    // imaginary literals like `3j`, only the imaginary part is stored
+    Imaginary(f64),
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -24,6 +143,8 @@ pub enum Keyword {
     And,
     As,
     Assert,
+    Async,
+    Await,
     Break,
     Class,
     Continue,
@@ -53,6 +174,7 @@ pub enum Keyword {
     Yield,
 }
 impl Keyword {
+    #[allow(dead_code)]
     fn len(&self) -> usize {
         match self {
             Keyword::False => "False".len(),
@@ -61,6 +183,8 @@ impl Keyword {
             Keyword::And => "and".len(),
             Keyword::As => "as".len(),
             Keyword::Assert => "assert".len(),
+            Keyword::Async => "async".len(),
+            Keyword::Await => "await".len(),
             Keyword::Break => "break".len(),
             Keyword::Class => "class".len(),
             Keyword::Continue => "continue".len(),
@@ -102,259 +226,728 @@ pub enum Symbol {
     RightCurlyBrace,
     Comma,
     Dot,
+    Ellipsis,
     Colon,
     SemiColon,
+    Arrow,
+    Walrus,
     Assign,
     Plus,
     Minus,
     Multiply,
     Divide,
+    FloorDivide,
     Modulo,
     Exponent,
+    MatrixMultiply,
     LessThan,
     GreaterThan,
     LessThanOrEqual,
     GreaterThanOrEqual,
     Equal,
     NotEqual,
-    Increment,
-    Decrement,
-    LogicalAnd,
-    LogicalOr,
     BitwiseAnd,
     BitwiseOr,
     BitwiseXor,
     BitwiseNot,
     ShiftLeft,
     ShiftRight,
-    SingleQuote,
-    DoubleQuote,
-    SendMessage,
-    HashTag,
-    Dollar,
-    AtSign,
-    InterrogationMark,
-    Crasis,
-    UnderLine,
+    // augmented assignments
+    PlusAssign,
+    MinusAssign,
+    MultiplyAssign,
+    DivideAssign,
+    FloorDivideAssign,
+    ModuloAssign,
+    ExponentAssign,
+    MatrixMultiplyAssign,
+    BitwiseAndAssign,
+    BitwiseOrAssign,
+    BitwiseXorAssign,
+    ShiftLeftAssign,
+    ShiftRightAssign,
 }
 
 impl Symbol {
     fn len(&self) -> usize {
-        match self {
-            Symbol::LeftParen => 1,
-            Symbol::RightParen => 1,
-            Symbol::LeftBracket => 1,
-            Symbol::RightBracket => 1,
-            Symbol::LeftCurlyBrace => 1,
-            Symbol::RightCurlyBrace => 1,
-            Symbol::Comma => 1,
-            Symbol::Dot => 1,
-            Symbol::Colon => 1,
-            Symbol::SemiColon => 1,
-            Symbol::Assign => 1,
-            Symbol::Plus => 1,
-            Symbol::Minus => 1,
-            Symbol::Multiply => 1,
-            Symbol::Divide => 1,
-            Symbol::Modulo => 1,
-            Symbol::Exponent => 1,
-            Symbol::LessThan => 1,
-            Symbol::GreaterThan => 1,
-            Symbol::LessThanOrEqual => 2,
-            Symbol::GreaterThanOrEqual => 2,
-            Symbol::Equal => 2,
-            Symbol::NotEqual => 2,
-            Symbol::Increment => 2,
-            Symbol::Decrement => 2,
-            Symbol::LogicalAnd => 2,
-            Symbol::LogicalOr => 2,
-            Symbol::BitwiseAnd => 1,
-            Symbol::BitwiseOr => 1,
-            Symbol::BitwiseXor => 1,
-            Symbol::BitwiseNot => 1,
-            Symbol::ShiftLeft => 2,
-            Symbol::ShiftRight => 2,
-            Symbol::SingleQuote => 1,
-            Symbol::DoubleQuote => 1,
-            Symbol::SendMessage => 1,
-            Symbol::HashTag => 1,
-            Symbol::Dollar => 1,
-            Symbol::AtSign => 1,
-            Symbol::InterrogationMark => 1,
-            Symbol::Crasis => 1,
-            Symbol::UnderLine => 1,
-        }
+        reverse_symbol(self).len()
     }
 }
 
-#[derive(Debug)]
+// Python 3 operators and delimiters, longest first so the lexer always takes
+// the longest match (`**=` before `**` before `*`).
+const SYMBOLS: [(&str, Symbol); 47] = [
+    ("**=", Symbol::ExponentAssign),
+    ("//=", Symbol::FloorDivideAssign),
+    (">>=", Symbol::ShiftRightAssign),
+    ("<<=", Symbol::ShiftLeftAssign),
+    ("...", Symbol::Ellipsis),
+    ("->", Symbol::Arrow),
+    (":=", Symbol::Walrus),
+    ("**", Symbol::Exponent),
+    ("//", Symbol::FloorDivide),
+    ("<<", Symbol::ShiftLeft),
+    (">>", Symbol::ShiftRight),
+    ("<=", Symbol::LessThanOrEqual),
+    (">=", Symbol::GreaterThanOrEqual),
+    ("==", Symbol::Equal),
+    ("!=", Symbol::NotEqual),
+    ("+=", Symbol::PlusAssign),
+    ("-=", Symbol::MinusAssign),
+    ("*=", Symbol::MultiplyAssign),
+    ("/=", Symbol::DivideAssign),
+    ("%=", Symbol::ModuloAssign),
+    ("@=", Symbol::MatrixMultiplyAssign),
+    ("&=", Symbol::BitwiseAndAssign),
+    ("|=", Symbol::BitwiseOrAssign),
+    ("^=", Symbol::BitwiseXorAssign),
+    ("(", Symbol::LeftParen),
+    (")", Symbol::RightParen),
+    ("[", Symbol::LeftBracket),
+    ("]", Symbol::RightBracket),
+    ("{", Symbol::LeftCurlyBrace),
+    ("}", Symbol::RightCurlyBrace),
+    (",", Symbol::Comma),
+    (".", Symbol::Dot),
+    (":", Symbol::Colon),
+    (";", Symbol::SemiColon),
+    ("=", Symbol::Assign),
+    ("+", Symbol::Plus),
+    ("-", Symbol::Minus),
+    ("*", Symbol::Multiply),
+    ("/", Symbol::Divide),
+    ("%", Symbol::Modulo),
+    ("@", Symbol::MatrixMultiply),
+    ("<", Symbol::LessThan),
+    (">", Symbol::GreaterThan),
+    ("&", Symbol::BitwiseAnd),
+    ("|", Symbol::BitwiseOr),
+    ("^", Symbol::BitwiseXor),
+    ("~", Symbol::BitwiseNot),
+];
+
+/// Problem found while reading the source, like an invalid character or a
+/// malformed literal.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TokenizeError {
+    pub kind: TokenizeErrorKind,
+    pub message: String,
+    pub span: Span,
+    // char at the beginning of the span, if any
+    pub character: Option<char>,
+}
+
+/// What went wrong, so the errors can be told apart without reading their message.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TokenizeErrorKind {
+    /// Tabs and spaces mixed so the indentation depends on the tab size.
+    InconsistentTabs,
+    /// A dedent to a column that no enclosing block starts at.
+    BadDedent,
+    /// A closing bracket with no opening one.
+    UnmatchedBracket,
+    /// A closing bracket of another kind than the last opening one.
+    MismatchedBracket,
+    /// An opening bracket still open at the end of the input.
+    UnclosedBracket,
+    /// Any other text that isn't a token, like a malformed literal.
+    InvalidToken,
+}
+
+impl fmt::Display for TokenizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.span)
+    }
+}
+
+#[derive(Clone, Debug)]
 pub enum ParseError {
-    UnexpectedToken(Token),
-    UnexpectedEndOfInput,
-    RecursionLimitExceeded,
+    UnexpectedToken(Token, Span),
+    // what the parser was looking for, like "':'" or "a name", and the token found instead
+    ExpectedToken(String, Token, Span),
+    // tokens that are valid one by one but not together, like `f(a=1, 2)`
+    InvalidSyntax(String, Span),
+    UnexpectedEndOfInput(Span),
+    RecursionLimitExceeded(Span),
+}
+
+impl ParseError {
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnexpectedToken(_, span)
+            | ParseError::ExpectedToken(_, _, span)
+            | ParseError::InvalidSyntax(_, span)
+            | ParseError::UnexpectedEndOfInput(span)
+            | ParseError::RecursionLimitExceeded(span) => *span,
+        }
+    }
 }
 
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            ParseError::UnexpectedToken(token) => write!(f, "Unexpected token: {:?}", token),
-            ParseError::UnexpectedEndOfInput => write!(f, "Unexpected end of input"),
-            ParseError::RecursionLimitExceeded => write!(f, "Recursion limit exceeded"),
+            ParseError::UnexpectedToken(token, span) => write!(f, "Unexpected token: {:?} at {}", token, span),
+            ParseError::ExpectedToken(expected, token, span) => write!(f, "Expected {}, found {:?} at {}", expected, token, span),
+            ParseError::InvalidSyntax(message, span) => write!(f, "{} at {}", message, span),
+            ParseError::UnexpectedEndOfInput(span) => write!(f, "Unexpected end of input at {}", span),
+            ParseError::RecursionLimitExceeded(span) => write!(f, "Recursion limit exceeded at {}", span),
         }
     }
 }
 
-fn parse_string(input: &str) -> Option<String> {
-    match input.chars().nth(0) {
-        Some('"') | Some('\'') => {
-            let mut literal = String::new();
-            let mut iter = input.chars().skip(1);
-            while let Some(ch) = iter.next() {
-                if ch == '"' || ch == '\'' {
-                    break;
-                }
-                literal.push(ch);
-            }
-            Some(literal)
+// Prefixes of a string literal, they are case insensitive and can be combined like `rb''`
+#[derive(Clone, Copy, Debug, Default)]
+struct StringPrefix {
+    raw: bool,
+    bytes: bool,
+    formatted: bool,
+}
+
+fn parse_string_prefix(input: &str) -> Option<(StringPrefix, usize)> {
+    let (quote, _) = input.char_indices().take(3).find(|(_, ch)| *ch == '"' || *ch == '\'')?;
+    let mut prefix = StringPrefix::default();
+    match input[..quote].to_ascii_lowercase().as_str() {
+        "" | "u" => {}
+        "r" => prefix.raw = true,
+        "b" => prefix.bytes = true,
+        "f" => prefix.formatted = true,
+        "br" | "rb" => {
+            prefix.raw = true;
+            prefix.bytes = true;
+        }
+        "fr" | "rf" => {
+            prefix.raw = true;
+            prefix.formatted = true;
         }
-        _ => None
+        _ => return None,
     }
+    Some((prefix, quote))
 }
 
-fn parse_special(input: &str) -> Option<Token> {
-    match input.chars().nth(0)?.to_string().as_str() {
-        "\n" => Some(Token::LineBreak),
-        "\r" => Some(Token::CarriageReturn),
-        " " => Some(Token::Space),
-        "\\" => Some(Token::Slash),
-        "\t" => Some(Token::IdentationTab),
+// escapes made by a single char, shared by str and bytes literals
+fn simple_escape(ch: char) -> Option<char> {
+    match ch {
+        '\\' => Some('\\'),
+        '\'' => Some('\''),
+        '"' => Some('"'),
+        'a' => Some('\x07'),
+        'b' => Some('\x08'),
+        'f' => Some('\x0c'),
+        'n' => Some('\n'),
+        'r' => Some('\r'),
+        't' => Some('\t'),
+        'v' => Some('\x0b'),
         _ => None,
     }
 }
 
-fn parse_symbol(input: &str) -> Option<Symbol> {
-    let (first, second) = input.split_at(1);
-    // let (first_letter, second): (char, char) = "ab".chars().collect_tuple().unwrap();
-    match first {
-        "^" => Some(Symbol::BitwiseXor),
-        "~" => Some(Symbol::BitwiseNot),
-        "'" => Some(Symbol::SingleQuote),
-        "!" => Some(Symbol::SendMessage),
-        "\"" => Some(Symbol::DoubleQuote),
-        "!" => {
-            match second {
-                "=" => Some(Symbol::NotEqual),
-                _ => None,
+fn read_hex(chars: &mut std::iter::Peekable<std::str::Chars>, count: usize) -> Option<u32> {
+    let mut value = 0;
+    for _ in 0..count {
+        let digit = chars.next()?.to_digit(16)?;
+        value = value * 16 + digit;
+    }
+    Some(value)
+}
+
+// Decode the backslash escapes of a literal body into code points. Bytes
+// literals don't know about `\N`, `\u` and `\U`, so those are kept as written.
+fn decode_escapes(body: &str, bytes: bool) -> Result<Vec<u32>, String> {
+    let mut decoded = Vec::new();
+    let mut chars = body.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch != '\\' {
+            decoded.push(ch as u32);
+            continue;
+        }
+        let Some(escape) = chars.next() else {
+            decoded.push('\\' as u32);
+            break;
+        };
+        match escape {
+            // a backslash at the end of the line continues the string into the next one
+            '\n' => {}
+            '0'..='7' => {
+                let mut value = escape.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match chars.peek().and_then(|ch| ch.to_digit(8)) {
+                        Some(digit) => {
+                            value = value * 8 + digit;
+                            chars.next();
+                        }
+                        None => break,
+                    }
+                }
+                if bytes && value > 0xff {
+                    return Err(format!("octal escape \\{:o} is out of range for bytes", value));
+                }
+                decoded.push(value);
+            }
+            'x' => match read_hex(&mut chars, 2) {
+                Some(value) => decoded.push(value),
+                None => return Err("truncated \\xXX escape".to_string()),
+            },
+            'u' | 'U' if !bytes => {
+                let (count, name) = if escape == 'u' { (4, "\\uXXXX") } else { (8, "\\UXXXXXXXX") };
+                match read_hex(&mut chars, count) {
+                    // surrogates are valid in Python strings, they can't be held in a Rust one
+                    Some(value) if value <= char::MAX as u32 => decoded.push(value),
+                    Some(_) => return Err("illegal Unicode character".to_string()),
+                    None => return Err(format!("truncated {} escape", name)),
+                }
             }
+            'N' if !bytes => {
+                if chars.next() != Some('{') {
+                    return Err("malformed \\N character escape".to_string());
+                }
+                let mut name = String::new();
+                loop {
+                    match chars.next() {
+                        Some('}') => break,
+                        Some(ch) => name.push(ch),
+                        None => return Err("malformed \\N character escape".to_string()),
+                    }
+                }
+                match unicode_names2::character(&name) {
+                    Some(ch) => decoded.push(ch as u32),
+                    None => return Err(format!("unknown Unicode character name '{}'", name)),
+                }
+            }
+            _ => match simple_escape(escape) {
+                Some(ch) => decoded.push(ch as u32),
+                // unknown escapes are kept untouched
+                None => {
+                    decoded.push('\\' as u32);
+                    decoded.push(escape as u32);
+                }
+            },
         }
-        "|" => {
-            match second {
-                "|" => Some(Symbol::LogicalOr),
-                _ => Some(Symbol::BitwiseOr),
+    }
+    Ok(decoded)
+}
+
+// Build the token for the body of a literal already delimited by its quotes.
+fn string_token(body: &str, prefix: StringPrefix) -> Result<Token, String> {
+    if prefix.bytes {
+        if !body.is_ascii() {
+            return Err("bytes can only contain ASCII literal characters".to_string());
+        }
+        if prefix.raw {
+            return Ok(Token::Bytes(body.as_bytes().to_vec()));
+        }
+        let decoded = decode_escapes(body, true)?;
+        return Ok(Token::Bytes(decoded.into_iter().map(|byte| byte as u8).collect()));
+    }
+    Ok(Token::String(decode_text(body, prefix.raw)?))
+}
+
+fn decode_text(body: &str, raw: bool) -> Result<String, String> {
+    if raw {
+        return Ok(body.to_string());
+    }
+    // the only code points that aren't chars are the surrogates, which are
+    // replaced like a lossy UTF-8 decoding would
+    let decoded = decode_escapes(body, false)?;
+    Ok(decoded.into_iter().map(|code| char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER)).collect())
+}
+
+// Splits the body of an f-string into literal text and replacement fields. The
+// input starts at the opening quote and `pos` always points into it.
+struct FStringScanner<'a> {
+    input: &'a str,
+    pos: usize,
+    closing: &'a str,
+    raw: bool,
+    // position of the beginning of the input into the source
+    cursor: Cursor,
+}
+
+impl<'a> FStringScanner<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn cursor_at(&self, pos: usize) -> Cursor {
+        let mut cursor = self.cursor;
+        cursor.advance(&self.input[..pos]);
+        cursor
+    }
+
+    fn unterminated(&self) -> String {
+        if self.closing.len() == 1 {
+            "unterminated f-string literal".to_string()
+        } else {
+            "unterminated triple-quoted f-string literal".to_string()
+        }
+    }
+
+    // Read text and replacement fields until the closing quote, or until the `}`
+    // that ends a format spec.
+    fn parts(&mut self, in_spec: bool) -> Result<Vec<FStringPart>, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        loop {
+            let rest = self.rest();
+            let Some(ch) = rest.chars().next() else {
+                return Err(self.unterminated());
+            };
+            if rest.starts_with(self.closing) {
+                if in_spec {
+                    return Err("f-string: expecting '}'".to_string());
+                }
+                self.pos += self.closing.len();
+                break;
             }
+            match ch {
+                '}' if in_spec => break,
+                '{' if rest.starts_with("{{") => {
+                    literal.push('{');
+                    self.pos += 2;
+                }
+                '}' if rest.starts_with("}}") => {
+                    literal.push('}');
+                    self.pos += 2;
+                }
+                '}' => return Err("f-string: single '}' is not allowed".to_string()),
+                '{' => {
+                    if !literal.is_empty() {
+                        parts.push(FStringPart::Literal(decode_text(&literal, self.raw)?));
+                        literal.clear();
+                    }
+                    parts.push(FStringPart::Expression(self.replacement_field()?));
+                }
+                '\n' if self.closing.len() == 1 => return Err(self.unterminated()),
+                // keep escapes as written, they are decoded with the whole text
+                '\\' => {
+                    let escaped = rest[1..].chars().next().ok_or_else(|| self.unterminated())?;
+                    literal.push('\\');
+                    literal.push(escaped);
+                    self.pos += 1 + escaped.len_utf8();
+                }
+                _ => {
+                    literal.push(ch);
+                    self.pos += ch.len_utf8();
+                }
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(FStringPart::Literal(decode_text(&literal, self.raw)?));
         }
-        "<" => {
-            match second {
-                "<" => Some(Symbol::ShiftLeft),
-                "=" => Some(Symbol::LessThanOrEqual),
-                _ => Some(Symbol::LessThan),
+        Ok(parts)
+    }
+
+    // Read a `{expression=!conversion:spec}` field, `pos` is at the opening brace.
+    fn replacement_field(&mut self) -> Result<FStringExpression, String> {
+        self.pos += 1;
+        let start = self.pos;
+        let mut depth = 0;
+        loop {
+            let rest = self.rest();
+            let Some(ch) = rest.chars().next() else {
+                return Err(self.unterminated());
+            };
+            match ch {
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' if depth > 0 => depth -= 1,
+                '}' | ':' if depth == 0 => break,
+                '!' if depth == 0 && !rest.starts_with("!=") => break,
+                // `{value=}` but not `==`, `!=`, `<=` or `>=`
+                '=' if depth == 0
+                    && !rest.starts_with("==")
+                    && !self.input[..self.pos].ends_with(['=', '!', '<', '>']) => break,
+                '#' => return Err("f-string expression part cannot include '#'".to_string()),
+                '\n' if self.closing.len() == 1 => return Err(self.unterminated()),
+                // nested strings can use any quote, even the one of the f-string
+                '\'' | '"' => match parse_string(rest, self.cursor_at(self.pos)) {
+                    Some(Ok((_, len))) => {
+                        self.pos += len;
+                        continue;
+                    }
+                    Some(Err((message, _))) => return Err(message),
+                    None => {}
+                },
+                _ => {}
             }
+            self.pos += ch.len_utf8();
+        }
+        let text = &self.input[start..self.pos];
+        if text.trim().is_empty() {
+            return Err("f-string: valid expression required before '}'".to_string());
         }
-        ">" => {
-            match second {
-                ">" => Some(Symbol::ShiftRight),
-                "=" => Some(Symbol::GreaterThanOrEqual),
-                _ => Some(Symbol::GreaterThan),
+        let tokens = tokenize_embedded(text, self.cursor_at(start))?;
+
+        let mut field = FStringExpression {
+            tokens,
+            debug_text: None,
+            conversion: None,
+            format_spec: None,
+        };
+        if self.rest().starts_with('=') {
+            self.pos += 1;
+            self.pos += self.rest().len() - self.rest().trim_start().len();
+            field.debug_text = Some(self.input[start..self.pos].to_string());
+        }
+        if self.rest().starts_with('!') {
+            match self.rest()[1..].chars().next() {
+                Some(conversion @ ('s' | 'r' | 'a')) => field.conversion = Some(conversion),
+                _ => return Err("f-string: invalid conversion character: expected 's', 'r', or 'a'".to_string()),
             }
+            self.pos += 2;
+        }
+        if self.rest().starts_with(':') {
+            self.pos += 1;
+            field.format_spec = Some(self.parts(true)?);
+        }
+        if !self.rest().starts_with('}') {
+            return Err("f-string: expecting '}'".to_string());
+        }
+        self.pos += 1;
+        Ok(field)
+    }
+}
+
+// Tokens of an expression embedded in an f-string, `cursor` is where it starts.
+fn tokenize_embedded(text: &str, cursor: Cursor) -> Result<Vec<SpannedToken>, String> {
+    let mut state = LexerState::new();
+    state.cursor = cursor;
+    state.at_line_start = false;
+    // the expression is inside the braces of the field, so it can span lines
+    state.brackets.push(('{', cursor.span()));
+    let mut scanner = Scanner::new(text, TokenizerOptions::default(), &mut state);
+    scanner.run();
+    scanner.state.brackets.pop();
+    scanner.finish();
+    let mut tokens = Vec::new();
+    for item in state.items {
+        match item {
+            Ok(spanned) if spanned.token == Token::LineBreak => {}
+            Ok(spanned) => tokens.push(spanned),
+            Err(error) => return Err(error.message),
         }
-        "&" => {
-            match second {
-                "&" => Some(Symbol::LogicalAnd),
-                _ => Some(Symbol::BitwiseAnd),
+    }
+    Ok(tokens)
+}
+
+// Try to read a string literal (with an optional prefix) at the beginning of the
+// input, returning its token and how many bytes it takes. A malformed literal
+// returns the error message and how many bytes to skip to get past it. `cursor`
+// is the position of the input, used by the expressions embedded into f-strings.
+fn parse_string(input: &str, cursor: Cursor) -> Option<Result<(Token, usize), (String, usize)>> {
+    let (prefix, start) = parse_string_prefix(input)?;
+    let quote = input[start..].chars().next()?;
+    let triple = quote.to_string().repeat(3);
+    let closing = if input[start..].starts_with(&triple) { triple.as_str() } else { &triple[..1] };
+    let body_start = start + closing.len();
+
+    if prefix.formatted {
+        let mut scanner = FStringScanner {
+            input,
+            pos: body_start,
+            closing,
+            raw: prefix.raw,
+            cursor,
+        };
+        return Some(match scanner.parts(false) {
+            Ok(parts) => Ok((Token::FString(parts), scanner.pos)),
+            // skip up to the closing quote, or the end of the line when it's missing
+            Err(message) => {
+                let rest = &input[scanner.pos.min(input.len())..];
+                let skip = match (rest.find(closing), rest.find('\n')) {
+                    (Some(end), Some(newline)) if closing.len() == 1 && newline < end => newline,
+                    (Some(end), _) => end + closing.len(),
+                    (None, Some(newline)) if closing.len() == 1 => newline,
+                    (None, _) => rest.len(),
+                };
+                Err((message, scanner.pos + skip))
             }
-        },
-        
-        "`" => Some(Symbol::Crasis),
-        "@" => Some(Symbol::AtSign),
-        "?" => Some(Symbol::InterrogationMark),
-        "$" => Some(Symbol::Dollar),
-        "#" => Some(Symbol::HashTag),
-        "(" => Some(Symbol::LeftParen),
-        ")" => Some(Symbol::RightParen),
-        "[" => Some(Symbol::LeftBracket),
-        "]" => Some(Symbol::RightBracket),
-        "{" => Some(Symbol::LeftCurlyBrace),
-        "}" => Some(Symbol::RightCurlyBrace),
-        "," => Some(Symbol::Comma),
-        "." => Some(Symbol::Dot),
-        ":" => Some(Symbol::Colon),
-        ";" => Some(Symbol::SemiColon),
-        "=" => {
-            match second {
-                "=" => Some(Symbol::Equal),
-                "+" => Some(Symbol::Increment),
-                "-" => Some(Symbol::Decrement),
-                _ => Some(Symbol::Assign)
+        });
+    }
+
+    let mut chars = input[body_start..].char_indices();
+    while let Some((index, ch)) = chars.next() {
+        match ch {
+            // the escaped char never closes the literal, even in raw strings
+            '\\' => {
+                chars.next();
+            }
+            '\n' if closing.len() == 1 => break,
+            _ if input[body_start + index..].starts_with(closing) => {
+                let body = &input[body_start..body_start + index];
+                let len = body_start + index + closing.len();
+                return Some(match string_token(body, prefix) {
+                    Ok(token) => Ok((token, len)),
+                    Err(message) => Err((message, len)),
+                });
             }
-        },
-        "+" => Some(Symbol::Plus),
-        "-" => Some(Symbol::Minus),
-        "_" => Some(Symbol::UnderLine),
-        "*" => Some(Symbol::Multiply),
-        "/" => Some(Symbol::Divide),
-        "%" => Some(Symbol::Modulo),
-        "^" => Some(Symbol::Exponent),
+            _ => {}
+        }
+    }
+    if closing.len() == 1 {
+        let len = input.find('\n').unwrap_or(input.len());
+        Some(Err(("unterminated string literal".to_string(), len)))
+    } else {
+        Some(Err(("unterminated triple-quoted string literal".to_string(), input.len())))
+    }
+}
+
+fn is_decimal(ch: u8) -> bool {
+    ch.is_ascii_digit()
+}
+
+fn is_binary(ch: u8) -> bool {
+    matches!(ch, b'0' | b'1')
+}
+
+fn is_octal(ch: u8) -> bool {
+    matches!(ch, b'0'..=b'7')
+}
+
+fn is_hexadecimal(ch: u8) -> bool {
+    ch.is_ascii_hexdigit()
+}
+
+// a number can't be directly followed by a letter, digit or underscore
+fn is_identifier_char(ch: u8) -> bool {
+    ch.is_ascii_alphanumeric() || ch == b'_' || ch >= 0x80
+}
+
+// Consume `digit (["_"] digit)*` starting at `pos` and return the position after it.
+// An underscore must always sit between two digits.
+fn scan_digits(input: &[u8], mut pos: usize, is_digit: fn(u8) -> bool) -> usize {
+    while pos < input.len() {
+        if is_digit(input[pos]) {
+            pos += 1;
+        } else if input[pos] == b'_' && pos + 1 < input.len() && is_digit(input[pos + 1]) {
+            pos += 2;
+        } else {
+            break;
+        }
+    }
+    pos
+}
+
+// The literal is well formed at this point, only its size is left to check:
+// the interpreter fails when it evaluates an integer too large for it.
+fn parse_integer(literal: &str, digits: &str, radix: u32) -> Number {
+    match i64::from_str_radix(&digits.replace('_', ""), radix) {
+        Ok(integer) => Number::Integer(integer),
+        Err(_) => Number::LargeInteger(literal.to_string()),
+    }
+}
+
+// Numbers prefixed with 0x, 0o or 0b.
+fn parse_radix_number(input: &[u8]) -> Result<(Number, usize), String> {
+    let (radix, name, is_digit): (u32, &str, fn(u8) -> bool) = match input[1] {
+        b'x' | b'X' => (16, "hexadecimal", is_hexadecimal),
+        b'o' | b'O' => (8, "octal", is_octal),
+        _ => (2, "binary", is_binary),
+    };
+    // an underscore is also allowed right after the prefix
+    let start = if input.get(2) == Some(&b'_') { 3 } else { 2 };
+    if !input.get(start).copied().is_some_and(is_digit) {
+        return Err(format!("invalid {} literal", name));
+    }
+    let end = scan_digits(input, start, is_digit);
+    if let Some(&next) = input.get(end) {
+        if next.is_ascii_digit() {
+            return Err(format!("invalid digit '{}' in {} literal", next as char, name));
+        }
+        if is_identifier_char(next) {
+            return Err(format!("invalid {} literal", name));
+        }
+    }
+    let literal = std::str::from_utf8(&input[..end]).unwrap();
+    Ok((parse_integer(literal, &literal[start..], radix), end))
+}
+
+// Decimal integers, floats with optional fraction and exponent, and imaginary numbers.
+fn parse_decimal_number(input: &[u8]) -> Result<(Number, usize), String> {
+    let invalid = || "invalid decimal literal".to_string();
+    let mut pos = scan_digits(input, 0, is_decimal);
+    let mut is_float = false;
+
+    if input.get(pos) == Some(&b'.') {
+        is_float = true;
+        pos += 1;
+        if input.get(pos).copied().is_some_and(is_decimal) {
+            pos = scan_digits(input, pos, is_decimal);
+        }
+    }
+    if let Some(b'e' | b'E') = input.get(pos) {
+        let mut exponent = pos + 1;
+        if let Some(b'+' | b'-') = input.get(exponent) {
+            exponent += 1;
+        }
+        if !input.get(exponent).copied().is_some_and(is_decimal) {
+            return Err(invalid());
+        }
+        is_float = true;
+        pos = scan_digits(input, exponent, is_decimal);
+    }
+    let text = std::str::from_utf8(&input[..pos]).unwrap().replace('_', "");
+    // the leading zero rule is for integers only, `01j` is an imaginary number
+    let number = if let Some(b'j' | b'J') = input.get(pos) {
+        pos += 1;
+        Number::Imaginary(text.parse::<f64>().map_err(|_| invalid())?)
+    } else if is_float {
+        Number::Float(text.parse::<f64>().map_err(|_| invalid())?)
+    } else {
+        if text.starts_with('0') && text.bytes().any(|ch| ch != b'0') {
+            return Err("leading zeros in decimal integer literals are not permitted; use an 0o prefix for octal integers".to_string());
+        }
+        parse_integer(std::str::from_utf8(&input[..pos]).unwrap(), &text, 10)
+    };
+    if input.get(pos).copied().is_some_and(is_identifier_char) {
+        return Err(invalid());
+    }
+    Ok((number, pos))
+}
+
+// Try to read a numeric literal at the beginning of the input, returning the
+// number and how many bytes it takes, or a message when the literal is malformed.
+fn parse_number(input: &str) -> Option<Result<(Number, usize), String>> {
+    let bytes = input.as_bytes();
+    match bytes {
+        [b'0', b'x' | b'X' | b'o' | b'O' | b'b' | b'B', ..] => Some(parse_radix_number(bytes)),
+        [b'0'..=b'9', ..] | [b'.', b'0'..=b'9', ..] => Some(parse_decimal_number(bytes)),
         _ => None,
     }
 }
 
-fn reverse_symbol(symbol: &Symbol) -> Option<String> {
-    match symbol {
-        Symbol::Increment => Some("=+".to_string()),
-        Symbol::Decrement => Some("=-".to_string()),
-        Symbol::Crasis => Some("`".to_string()),
-        Symbol::AtSign => Some("@".to_string()),
-        Symbol::InterrogationMark => Some("?".to_string()),
-        Symbol::Dollar => Some("$".to_string()),
-        Symbol::HashTag => Some("#".to_string()),
-        Symbol::LeftParen => Some("(".to_string()),
-        Symbol::RightParen => Some(")".to_string()),
-        Symbol::LeftBracket => Some("[".to_string()),
-        Symbol::RightBracket => Some("]".to_string()),
-        Symbol::LeftCurlyBrace => Some("{".to_string()),
-        Symbol::RightCurlyBrace => Some("}".to_string()),
-        Symbol::Comma => Some(",".to_string()),
-        Symbol::Dot => Some(".".to_string()),
-        Symbol::Colon => Some(":".to_string()),
-        Symbol::SemiColon => Some(";".to_string()),
-        Symbol::Assign => Some("=".to_string()),
-        Symbol::Plus => Some("+".to_string()),
-        Symbol::Minus => Some("-".to_string()),
-        Symbol::Multiply => Some("*".to_string()),
-        Symbol::Divide => Some("/".to_string()),
-        Symbol::Modulo => Some("%".to_string()),
-        Symbol::Exponent => Some("^".to_string()),
-        Symbol::LessThan => Some("<".to_string()),
-        Symbol::GreaterThan => Some(">".to_string()),
-        Symbol::LessThanOrEqual => Some("<=".to_string()),
-        Symbol::GreaterThanOrEqual => Some(">=".to_string()),
-        Symbol::Equal => Some("==".to_string()),
-        Symbol::NotEqual => Some("!=".to_string()),
-        Symbol::LogicalAnd => Some("&&".to_string()),
-        Symbol::LogicalOr => Some("||".to_string()),
-        Symbol::BitwiseAnd => Some("&".to_string()),
-        Symbol::BitwiseOr => Some("|".to_string()),
-        Symbol::BitwiseXor => Some("^".to_string()),
-        Symbol::BitwiseNot => Some("~".to_string()),
-        Symbol::ShiftLeft => Some("<<".to_string()),
-        Symbol::ShiftRight => Some(">>".to_string()),
-        Symbol::SingleQuote => Some("'".to_string()),
-        Symbol::SendMessage => Some("!".to_string()),
-        Symbol::DoubleQuote => Some("\"".to_string()),
-        Symbol::UnderLine => Some("_".to_string()),
+// Length of an identifier at the beginning of the input. Like Python, names
+// follow the Unicode XID_Start and XID_Continue properties (PEP 3131).
+fn identifier_len(input: &str) -> Option<usize> {
+    let first = input.chars().next()?;
+    if !(first == '_' || is_xid_start(first)) {
+        return None;
+    }
+    Some(input.find(|ch: char| !is_xid_continue(ch)).unwrap_or(input.len()))
+}
+
+fn parse_special(input: &str) -> Option<Token> {
+    match input.chars().next()?.to_string().as_str() {
+        "\r" => Some(Token::CarriageReturn),
+        " " | "\t" | "\x0c" => Some(Token::Space),
+        _ => None,
     }
 }
 
+// longest operator or delimiter at the beginning of the input
+fn parse_symbol(input: &str) -> Option<Symbol> {
+    SYMBOLS
+        .iter()
+        .find(|(text, _)| input.starts_with(text))
+        .map(|(_, symbol)| symbol.clone())
+}
+
+pub fn reverse_symbol(symbol: &Symbol) -> &'static str {
+    SYMBOLS
+        .iter()
+        .find(|(_, other)| other == symbol)
+        .map(|(text, _)| *text)
+        .unwrap()
+}
+
 fn parse_keyword(input: &str) -> Option<Keyword> {
     match input {
         "False" => Some(Keyword::False),
@@ -363,6 +956,8 @@ fn parse_keyword(input: &str) -> Option<Keyword> {
         "and" => Some(Keyword::And),
         "as" => Some(Keyword::As),
         "assert" => Some(Keyword::Assert),
+        "async" => Some(Keyword::Async),
+        "await" => Some(Keyword::Await),
         "break" => Some(Keyword::Break),
         "class" => Some(Keyword::Class),
         "continue" => Some(Keyword::Continue),
@@ -394,105 +989,787 @@ fn parse_keyword(input: &str) -> Option<Keyword> {
     }
 }
 
-fn reverse_keyword(keyword: &Keyword) -> Option<String> {
+/// Names that are keywords only in some places, like `match` at the start of
+/// a match statement. The tokenizer keeps them as identifiers so they can still
+/// be used as names, and the parser decides from the context.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SoftKeyword {
+    Match,
+    Case,
+    // the wildcard pattern
+    Underscore,
+    Type,
+}
+
+pub fn parse_soft_keyword(input: &str) -> Option<SoftKeyword> {
+    match input {
+        "match" => Some(SoftKeyword::Match),
+        "case" => Some(SoftKeyword::Case),
+        "_" => Some(SoftKeyword::Underscore),
+        "type" => Some(SoftKeyword::Type),
+        _ => None,
+    }
+}
+
+pub fn reverse_keyword(keyword: &Keyword) -> &'static str {
     match keyword {
-        Keyword::False => Some("False".to_string()),
-        Keyword::None => Some("None".to_string()),
-        Keyword::True => Some("True".to_string()),
-        Keyword::And => Some("and".to_string()),
-        Keyword::As => Some("as".to_string()),
-        Keyword::Assert => Some("assert".to_string()),
-        Keyword::Break => Some("break".to_string()),
-        Keyword::Class => Some("class".to_string()),
-        Keyword::Continue => Some("continue".to_string()),
-        Keyword::Def => Some("def".to_string()),
-        Keyword::Del => Some("del".to_string()),
-        Keyword::Elif => Some("elif".to_string()),
-        Keyword::Else => Some("else".to_string()),
-        Keyword::Except => Some("except".to_string()),
-        Keyword::Finally => Some("finally".to_string()),
-        Keyword::For => Some("for".to_string()),
-        Keyword::From => Some("from".to_string()),
-        Keyword::Global => Some("global".to_string()),
-        Keyword::If => Some("if".to_string()),
-        Keyword::Import => Some("import".to_string()),
-        Keyword::In => Some("in".to_string()),
-        Keyword::Is => Some("is".to_string()),
-        Keyword::Lambda => Some("lambda".to_string()),
-        Keyword::Nonlocal => Some("nonlocal".to_string()),
-        Keyword::Not => Some("not".to_string()),
-        Keyword::Or => Some("or".to_string()),
-        Keyword::Pass => Some("pass".to_string()),
-        Keyword::Raise => Some("raise".to_string()),
-        Keyword::Return => Some("return".to_string()),
-        Keyword::Try => Some("try".to_string()),
-        Keyword::While => Some("while".to_string()),
-        Keyword::With => Some("with".to_string()),
-        Keyword::Yield => Some("yield".to_string()),
-    }
-}
-
-pub fn Tokenizer(input: &str) -> Vec<Token> {
-    
-    let mut input = input;
-    let mut tokens = Vec::new();
+        Keyword::False => "False",
+        Keyword::None => "None",
+        Keyword::True => "True",
+        Keyword::And => "and",
+        Keyword::As => "as",
+        Keyword::Assert => "assert",
+        Keyword::Async => "async",
+        Keyword::Await => "await",
+        Keyword::Break => "break",
+        Keyword::Class => "class",
+        Keyword::Continue => "continue",
+        Keyword::Def => "def",
+        Keyword::Del => "del",
+        Keyword::Elif => "elif",
+        Keyword::Else => "else",
+        Keyword::Except => "except",
+        Keyword::Finally => "finally",
+        Keyword::For => "for",
+        Keyword::From => "from",
+        Keyword::Global => "global",
+        Keyword::If => "if",
+        Keyword::Import => "import",
+        Keyword::In => "in",
+        Keyword::Is => "is",
+        Keyword::Lambda => "lambda",
+        Keyword::Nonlocal => "nonlocal",
+        Keyword::Not => "not",
+        Keyword::Or => "or",
+        Keyword::Pass => "pass",
+        Keyword::Raise => "raise",
+        Keyword::Return => "return",
+        Keyword::Try => "try",
+        Keyword::While => "while",
+        Keyword::With => "with",
+        Keyword::Yield => "yield",
+    }
+}
+
+// tabs move the column to the next multiple of TAB_SIZE, as in CPython
+const TAB_SIZE: usize = 8;
+
+// Indentation of a line measured twice: expanding tabs to TAB_SIZE and counting
+// tabs as a single column. Both measures must agree on the ordering of two
+// lines, otherwise the meaning of the code depends on the editor tab width.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+struct Indentation {
+    column: usize,
+    alt_column: usize,
+}
+
+/// Options to tune which tokens the tokenizer generates.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TokenizerOptions {
+    /// keep comments as `Token::Comment` trivia, useful for tooling
+    pub keep_comments: bool,
+    /// keep reading after an error, so all the errors of the source are reported
+    pub recover_errors: bool,
+    /// keep comments, whitespace, soft line breaks and the source text of every
+    /// token, so `untokenize` gives back the exact input
+    pub keep_trivia: bool,
+}
 
-    let identifier_regex = Regex::new(r"^[a-zA-Z_][a-zA-Z0-9_]*").unwrap();
-    let text_regex = Regex::new(r#"^[a-zA-Z0-9_]+"#).unwrap();
+// State kept between the pieces of input given to the lexer.
+#[derive(Debug)]
+struct LexerState {
+    cursor: Cursor,
+    indents: Vec<Indentation>,
+    // open brackets join the lines until they are closed
+    brackets: Vec<(char, Span)>,
+    at_line_start: bool,
+    // the current logical line already has tokens, so its end emits a LineBreak
+    line_has_tokens: bool,
+    // tokens and errors in source order, waiting to be pulled
+    items: VecDeque<Result<SpannedToken, TokenizeError>>,
+    // an error was found without `recover_errors`, nothing else is produced
+    stopped: bool,
+}
+
+impl LexerState {
+    fn new() -> Self {
+        LexerState {
+            cursor: Cursor::new(),
+            indents: vec![Indentation::default()],
+            brackets: Vec::new(),
+            at_line_start: true,
+            line_has_tokens: false,
+            items: VecDeque::new(),
+            stopped: false,
+        }
+    }
+}
 
-    dbg!(&input);
-    while !input.is_empty() {
-        match input.get(0..4) {
-            Some("    ") => {
-                tokens.push(Token::IncrementIdent);
-                input = &input[4..];
-                continue;
+// Reads the tokens of `input`, which starts at the cursor of the state.
+struct Scanner<'a> {
+    input: &'a str,
+    options: TokenizerOptions,
+    state: &'a mut LexerState,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(input: &'a str, options: TokenizerOptions, state: &'a mut LexerState) -> Self {
+        Scanner { input, options, state }
+    }
+
+    // tokens that don't cover any text, like Indent and Dedent
+    fn emit(&mut self, token: Token, span: Span) {
+        let raw = self.options.keep_trivia.then(String::new);
+        self.state.items.push_back(Ok(SpannedToken { token, span, raw }));
+    }
+
+    // consume `len` bytes from the input and attach the covered span to the token
+    fn push(&mut self, token: Token, len: usize) {
+        let raw = self.options.keep_trivia.then(|| self.input[..len].to_string());
+        let span = self.state.cursor.advance(&self.input[..len]);
+        self.input = &self.input[len..];
+        match token {
+            Token::Space | Token::LineBreak | Token::SoftLineBreak | Token::LineContinuation | Token::Comment(_) => {}
+            _ => self.state.line_has_tokens = true,
+        }
+        self.state.items.push_back(Ok(SpannedToken { token, span, raw }));
+    }
+
+    // consume `len` bytes of trivia, only kept as a token with `keep_trivia`
+    fn trivia(&mut self, token: Token, len: usize) {
+        if self.options.keep_trivia {
+            self.push(token, len);
+        } else {
+            self.skip(len);
+        }
+    }
+
+    // consume `len` bytes without generating a token
+    fn skip(&mut self, len: usize) {
+        self.state.cursor.advance(&self.input[..len]);
+        self.input = &self.input[len..];
+    }
+
+    // Report an error covering the next `len` bytes and skip them. Without
+    // recovery the rest of the input is dropped, so the tokenizer stops here.
+    fn error(&mut self, kind: TokenizeErrorKind, message: String, len: usize) {
+        let character = self.input.chars().next();
+        let span = self.state.cursor.clone().advance(&self.input[..len]);
+        self.state.items.push_back(Err(TokenizeError { kind, message, span, character }));
+        if self.options.recover_errors {
+            self.skip(len);
+        } else {
+            self.input = "";
+            self.state.stopped = true;
+        }
+    }
+
+    // Measure the leading whitespace of a line and compare it with the
+    // indentation stack, emitting Indent or Dedent tokens when it changes.
+    // Blank lines and lines with only a comment don't take part in the indentation.
+    fn indentation(&mut self) {
+        let mut current = Indentation::default();
+        let mut len = 0;
+        for ch in self.input.chars() {
+            match ch {
+                ' ' => {
+                    current.column += 1;
+                    current.alt_column += 1;
+                }
+                '\t' => {
+                    current.column = (current.column / TAB_SIZE + 1) * TAB_SIZE;
+                    current.alt_column += 1;
+                }
+                // form feed resets the column count
+                '\x0c' => current = Indentation::default(),
+                _ => break,
+            }
+            len += ch.len_utf8();
+        }
+        if len > 0 {
+            self.trivia(Token::Space, len);
+        }
+        self.state.at_line_start = false;
+
+        match self.input.chars().next() {
+            None | Some('\n') | Some('#') => return,
+            Some('\r') if self.input[1..].starts_with('\n') => return,
+            _ => {}
+        }
+
+        let top = *self.state.indents.last().unwrap();
+        if current.column > top.column {
+            if current.alt_column <= top.alt_column {
+                self.inconsistent_tabs();
+            }
+            self.state.indents.push(current);
+            let span = self.state.cursor.span();
+            self.emit(Token::Indent, span);
+        } else {
+            while current.column < self.state.indents.last().unwrap().column {
+                self.state.indents.pop();
+                let span = self.state.cursor.span();
+                self.emit(Token::Dedent, span);
+            }
+            let top = *self.state.indents.last().unwrap();
+            if current.column != top.column {
+                self.error(TokenizeErrorKind::BadDedent, "unindent does not match any outer indentation level".to_string(), 0);
+            } else if current.alt_column != top.alt_column {
+                self.inconsistent_tabs();
+            }
+        }
+    }
+
+    fn run(&mut self) {
+        while !self.input.is_empty() {
+            self.step();
+        }
+    }
+
+    // read the next token, or the indentation when a line starts
+    fn step(&mut self) {
+        if self.state.at_line_start {
+            self.indentation();
+            return;
+        }
+        let input = self.input;
+
+        if input.starts_with('\n') {
+            // lines are joined while a bracket is open
+            if !self.state.brackets.is_empty() {
+                self.trivia(Token::SoftLineBreak, 1);
+                return;
             }
-            _ => {
-                // dbg!(r);
+            // only logical lines generate a LineBreak
+            if self.state.line_has_tokens {
+                self.push(Token::LineBreak, 1);
+            } else {
+                self.trivia(Token::SoftLineBreak, 1);
             }
+            self.state.line_has_tokens = false;
+            self.state.at_line_start = true;
+            return;
         }
 
-        // symbols can have until 2 signs into syntax
-        let twotokens = match input.get(..2) {
-            Some(tokens) => tokens,
-            None => { input }
+        if input.starts_with('#') {
+            let len = input.find('\n').unwrap_or(input.len());
+            if self.options.keep_comments || self.options.keep_trivia {
+                self.push(Token::Comment(input[..len].to_string()), len);
+            } else {
+                self.skip(len);
+            }
+            return;
+        }
+
+        // a backslash at the end of the line joins it with the next one
+        if let Some(rest) = input.strip_prefix('\\') {
+            if rest.starts_with('\n') {
+                self.trivia(Token::LineContinuation, 2);
+            } else if rest.starts_with("\r\n") {
+                self.trivia(Token::LineContinuation, 3);
+            } else if rest.is_empty() {
+                self.error(TokenizeErrorKind::InvalidToken, "unexpected EOF while parsing".to_string(), 1);
+            } else {
+                self.error(TokenizeErrorKind::InvalidToken, "unexpected character after line continuation character".to_string(), 1);
+            }
+            return;
+        }
+
+        // string literals go before identifiers because of prefixes like `r` and `b`
+        if let Some(literal) = parse_string(input, self.state.cursor) {
+            match literal {
+                Ok((token, len)) => self.push(token, len),
+                Err((message, len)) => self.error(TokenizeErrorKind::InvalidToken, message, len),
+            }
+        } else if let Some(number) = parse_number(input) {
+            match number {
+                Ok((number, len)) => self.push(Token::Number(number), len),
+                Err(message) => {
+                    // skip the whole malformed literal
+                    let len = input
+                        .find(|ch: char| !(ch.is_alphanumeric() || ch == '_' || ch == '.'))
+                        .unwrap_or(input.len());
+                    self.error(TokenizeErrorKind::InvalidToken, message, len);
+                }
+            }
+        } else if let Some(special) = parse_special(input) {
+            self.push(special, 1);
+        } else if let Some(symbol) = parse_symbol(input) {
+            let len = symbol.len();
+            // a closing bracket without its opening one is skipped
+            if self.bracket(&symbol) {
+                self.push(Token::Symbol(symbol), len);
+            }
+        } else if let Some(len) = identifier_len(input) {
+            let identifier = &input[..len];
+            let token = match parse_keyword(identifier) {
+                Some(keyword) => Token::Keyword(keyword),
+                None if identifier.is_ascii() => Token::Identifier(identifier.to_string()),
+                // NFKC makes equivalent spellings, like `ﬁle` and `file`, the same name
+                None => Token::Identifier(identifier.nfkc().collect()),
+            };
+            self.push(token, len);
+        } else {
+            let ch = input.chars().next().unwrap();
+            self.error(TokenizeErrorKind::InvalidToken, format!("invalid character '{}' (U+{:04X})", ch, ch as u32), ch.len_utf8());
+        }
+    }
+
+    // keep track of the open brackets, closing ones must match the last opened
+    fn bracket(&mut self, symbol: &Symbol) -> bool {
+        let closing = match symbol {
+            Symbol::LeftParen => Some('('),
+            Symbol::LeftBracket => Some('['),
+            Symbol::LeftCurlyBrace => Some('{'),
+            _ => None,
+        };
+        if let Some(opening) = closing {
+            let span = self.state.cursor.span();
+            self.state.brackets.push((opening, span));
+            return true;
+        }
+        let (closing, opening) = match symbol {
+            Symbol::RightParen => (')', '('),
+            Symbol::RightBracket => (']', '['),
+            Symbol::RightCurlyBrace => ('}', '{'),
+            _ => return true,
         };
-        // if format string, ignore tokens and create a fstring token (literal string into AST)
-        if let Some(literal_string) = parse_string(&input) {
-            dbg!(literal_string.len());
-            tokens.push(Token::FString(literal_string.clone()));
-            input = &input[literal_string.len()+2..];
-        }
-        else if let Some(special) = parse_special(&input) {
-            tokens.push(special);
-            input = &input[1..];
-        } 
-        else if let Some(symbol) = parse_symbol(&twotokens) {
-            tokens.push(Token::Symbol(symbol.clone()));
-            input = &input[symbol.len()..];
-
-        } else if let Some(identifier) = identifier_regex.find(input) {
-            if let Some(keyword) = parse_keyword(&identifier.as_str().to_string()){
-                tokens.push(Token::Keyword(keyword.clone()));
+        match self.state.brackets.last() {
+            Some((open, _)) if *open == opening => {
+                self.state.brackets.pop();
+                true
             }
-            else {
-                tokens.push(Token::Identifier(identifier.as_str().to_string()));
+            Some((open, _)) => {
+                let message = format!("closing parenthesis '{}' does not match opening parenthesis '{}'", closing, open);
+                self.state.brackets.pop();
+                self.error(TokenizeErrorKind::MismatchedBracket, message, 1);
+                false
+            }
+            None => {
+                self.error(TokenizeErrorKind::UnmatchedBracket, format!("unmatched '{}'", closing), 1);
+                false
             }
-            input = &input[identifier.end()..];
-        } else if let Some(rest) = text_regex.find(input){
-            // Handle unknown or invalid tokens
-            tokens.push(Token::Text(rest.as_str().to_string()));
-            // dbg!(&tokens);
-            input = &input[rest.end()..];
         }
-        else {
-            panic!("Invalid token found");
+    }
+
+    fn inconsistent_tabs(&mut self) {
+        self.error(TokenizeErrorKind::InconsistentTabs, "inconsistent use of tabs and spaces in indentation".to_string(), 0);
+    }
+
+    // close the last logical line and all the blocks still open
+    fn finish(&mut self) {
+        if self.state.stopped {
+            return;
         }
+        if let Some(&(open, span)) = self.state.brackets.last() {
+            let character = Some(open);
+            let error = TokenizeError {
+                kind: TokenizeErrorKind::UnclosedBracket,
+                message: format!("'{}' was never closed", open),
+                span,
+                character,
+            };
+            self.state.items.push_back(Err(error));
+            if !self.options.recover_errors {
+                self.state.stopped = true;
+                return;
+            }
+        }
+        let span = self.state.cursor.span();
+        if self.state.line_has_tokens {
+            self.state.line_has_tokens = false;
+            self.emit(Token::LineBreak, span);
+        }
+        while self.state.indents.len() > 1 {
+            self.state.indents.pop();
+            self.emit(Token::Dedent, span);
+        }
+    }
+}
+
+/// Streaming tokenizer. The input can be fed in chunks, like the lines typed
+/// in a REPL, and the tokens are read on demand. Only complete lines are
+/// tokenized until `close` is called, so a `None` from `next` before that
+/// means more input is needed.
+#[derive(Debug)]
+pub struct Lexer {
+    options: TokenizerOptions,
+    state: LexerState,
+    // input fed so far, the part before `start` was already tokenized
+    buffer: String,
+    start: usize,
+    // end of the last complete line in the buffer
+    complete: usize,
+    closed: bool,
+}
+
+impl Lexer {
+    pub fn new(options: TokenizerOptions) -> Self {
+        Lexer {
+            options,
+            state: LexerState::new(),
+            buffer: String::new(),
+            start: 0,
+            complete: 0,
+            closed: false,
+        }
+    }
+
+    /// Append a piece of input, it doesn't need to end at a line boundary.
+    pub fn feed(&mut self, chunk: &str) {
+        assert!(!self.closed, "input fed to a closed lexer");
+        // drop what was already tokenized, spans are tracked by the cursor
+        if self.start > 0 {
+            self.buffer.drain(..self.start);
+            self.complete -= self.start;
+            self.start = 0;
+        }
+        let end = self.buffer.len();
+        self.buffer.push_str(chunk);
+        if let Some(newline) = chunk.rfind('\n') {
+            self.complete = end + newline + 1;
+        }
+    }
+
+    /// Mark the end of the input, closing the last line and the open blocks.
+    pub fn close(&mut self) {
+        self.closed = true;
+        self.complete = self.buffer.len();
+    }
+
+    // tokenize until there are `count` items waiting or more input is needed
+    fn fill(&mut self, count: usize) {
+        while self.state.items.len() < count && !self.state.stopped {
+            if self.start == self.complete {
+                if self.closed {
+                    let input = &self.buffer[self.start..];
+                    Scanner::new(input, self.options, &mut self.state).finish();
+                    // nothing is left after the end of the input
+                    self.state.stopped = true;
+                }
+                return;
+            }
+            let (cursor, queued) = (self.state.cursor, self.state.items.len());
+            let input = &self.buffer[self.start..self.complete];
+            let mut scanner = Scanner::new(input, self.options, &mut self.state);
+            scanner.step();
+            let rest = scanner.input.len();
+            // a token running until the end of the input, like an unterminated
+            // triple-quoted string, may be completed by the next chunk
+            let end = cursor.offset + input.len();
+            let incomplete = !self.closed
+                && self.state.items.range(queued..).any(|item| matches!(item, Err(error) if error.span.end == end));
+            if incomplete {
+                self.state.items.truncate(queued);
+                self.state.cursor = cursor;
+                self.state.stopped = false;
+                return;
+            }
+            self.start = self.complete - rest;
+        }
+    }
+}
+
+impl Iterator for Lexer {
+    type Item = Result<SpannedToken, TokenizeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.fill(1);
+        self.state.items.pop_front()
+    }
+}
+
+// Tokenize the input with the given options. Without `recover_errors` there is
+// at most one error and the tokens stop where it was found.
+pub fn tokenize_with(input: &str, options: TokenizerOptions) -> (Vec<SpannedToken>, Vec<TokenizeError>) {
+    let mut lexer = Lexer::new(options);
+    lexer.feed(input);
+    lexer.close();
+    let mut tokens = Vec::new();
+    let mut errors = Vec::new();
+    for item in lexer {
+        match item {
+            Ok(token) => tokens.push(token),
+            Err(error) => errors.push(error),
+        }
+    }
+    (tokens, errors)
+}
+
+/// Turn tokens back into source code. Tokens read with `keep_trivia` carry
+/// their source text, so the input comes back byte for byte. Other tokens,
+/// like the ones created by a refactoring tool, are written in a normalized
+/// form, indenting blocks with four spaces.
+pub fn untokenize(tokens: &[SpannedToken]) -> String {
+    let mut source = String::new();
+    let mut depth = 0;
+    let mut at_line_start = true;
+    for spanned in tokens {
+        match spanned.token {
+            Token::Indent => depth += 1,
+            Token::Dedent => depth -= 1,
+            _ => {}
+        }
+        if let Some(raw) = &spanned.raw {
+            source.push_str(raw);
+        } else {
+            if at_line_start && !matches!(spanned.token, Token::Indent | Token::Dedent | Token::Space) {
+                source.push_str(&"    ".repeat(depth));
+            }
+            source.push_str(&token_text(&spanned.token));
+        }
+        match spanned.token {
+            Token::LineBreak | Token::SoftLineBreak | Token::LineContinuation => at_line_start = true,
+            Token::Indent | Token::Dedent => {}
+            _ => at_line_start = false,
+        }
+    }
+    source
+}
+
+// source text for a token without its raw text
+fn token_text(token: &Token) -> String {
+    match token {
+        Token::Identifier(name) => name.clone(),
+        Token::Keyword(keyword) => reverse_keyword(keyword).to_string(),
+        Token::Symbol(symbol) => reverse_symbol(symbol).to_string(),
+        Token::Number(Number::Integer(integer)) => integer.to_string(),
+        Token::Number(Number::LargeInteger(literal)) => literal.clone(),
+        Token::Number(Number::Float(float)) => float_repr(*float),
+        Token::Number(Number::Imaginary(imaginary)) => format!("{}j", imaginary),
+        Token::String(text) => Value::String(text.clone()).repr(),
+        Token::Bytes(bytes) => Value::Bytes(bytes.clone()).repr(),
+        Token::FString(parts) => format!("f'{}'", fstring_text(parts)),
+        Token::Comment(comment) => comment.clone(),
+        Token::LineBreak | Token::SoftLineBreak => "\n".to_string(),
+        Token::LineContinuation => "\\\n".to_string(),
+        Token::Indent | Token::Dedent => String::new(),
+        Token::Space => " ".to_string(),
+        Token::CarriageReturn => "\r".to_string(),
     }
-    tokens
 }
 
+fn fstring_text(parts: &[FStringPart]) -> String {
+    let mut text = String::new();
+    for part in parts {
+        match part {
+            FStringPart::Literal(literal) => {
+                // escape like a single quoted string, braces are doubled
+                let repr = Value::String(literal.clone()).repr();
+                let body = &repr[1..repr.len() - 1];
+                let body = if repr.starts_with('"') { body.replace('\'', "\\'") } else { body.to_string() };
+                text.push_str(&body.replace('{', "{{").replace('}', "}}"));
+            }
+            FStringPart::Expression(field) => {
+                text.push('{');
+                match &field.debug_text {
+                    Some(debug_text) => text.push_str(debug_text),
+                    None => text.push_str(untokenize(&field.tokens).trim()),
+                }
+                if let Some(conversion) = field.conversion {
+                    text.push('!');
+                    text.push(conversion);
+                }
+                if let Some(spec) = &field.format_spec {
+                    text.push(':');
+                    text.push_str(&fstring_text(spec));
+                }
+                text.push('}');
+            }
+        }
+    }
+    text
+}
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imaginary_with_leading_zeros() {
+        assert_eq!(parse_number("01j"), Some(Ok((Number::Imaginary(1.0), 3))));
+        assert_eq!(parse_number("00j"), Some(Ok((Number::Imaginary(0.0), 3))));
+        assert_eq!(parse_number("007J"), Some(Ok((Number::Imaginary(7.0), 4))));
+        assert!(matches!(parse_number("01"), Some(Err(message)) if message.starts_with("leading zeros")));
+        assert_eq!(parse_number("00"), Some(Ok((Number::Integer(0), 2))));
+    }
 
+    #[test]
+    fn integers_too_large_for_i64() {
+        assert_eq!(parse_number("9223372036854775807"), Some(Ok((Number::Integer(i64::MAX), 19))));
+        assert_eq!(parse_number("9223372036854775808"), Some(Ok((Number::LargeInteger("9223372036854775808".to_string()), 19))));
+        assert_eq!(parse_number("0xFFFF_FFFF_FFFF_FFFF)"), Some(Ok((Number::LargeInteger("0xFFFF_FFFF_FFFF_FFFF".to_string()), 21))));
+        let source = "x = 0b1_0000000000000000000000000000000000000000000000000000000000000000\n";
+        let (tokens, errors) = tokenize_with(source, TokenizerOptions { keep_trivia: true, ..Default::default() });
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(untokenize(&tokens), source);
+    }
+
+    #[test]
+    fn surrogate_escapes() {
+        assert_eq!(decode_text("a\\uDC80\\ud83d", false), Ok("a\u{FFFD}\u{FFFD}".to_string()));
+        assert_eq!(decode_text("\\U0010FFFF", false), Ok("\u{10FFFF}".to_string()));
+        assert_eq!(decode_text("\\U00110000", false), Err("illegal Unicode character".to_string()));
+    }
+
+    #[test]
+    fn lexer_fed_in_chunks() {
+        let source = "def f(x):\n    s = \"\"\"a\nb\"\"\"\n    return (x +\n        1)\n";
+        let (expected, errors) = tokenize_with(source, TokenizerOptions::default());
+        assert!(errors.is_empty(), "{:?}", errors);
+        let mut lexer = Lexer::new(TokenizerOptions::default());
+        let mut tokens = Vec::new();
+        // split inside the triple-quoted string and inside the brackets
+        for chunk in ["def f(x):\n    s = \"\"\"a", "\nb\"\"\"\n    return (x +\n", "        1)\n"] {
+            lexer.feed(chunk);
+            tokens.extend(lexer.by_ref().map(Result::unwrap));
+        }
+        lexer.close();
+        tokens.extend(lexer.map(Result::unwrap));
+        assert_eq!(tokens, expected);
+    }
+
+    #[test]
+    fn token_spans() {
+        let (tokens, errors) = tokenize_with("if x:\n    y = 'é'\n", TokenizerOptions::default());
+        assert!(errors.is_empty(), "{:?}", errors);
+        let string = tokens.iter().find(|spanned| matches!(spanned.token, Token::String(_))).unwrap();
+        // columns count chars from 1, the offsets count bytes
+        assert_eq!(string.span, Span { start: 14, end: 18, line: 2, column: 9, end_line: 2, end_column: 12 });
+        let name = &tokens[2];
+        assert_eq!((&name.token, name.span.line, name.span.column, name.span.end_column), (&Token::Identifier("x".to_string()), 1, 4, 5));
+        // a line break ends at the start of the next line
+        let line_break = &tokens[4];
+        assert_eq!((&line_break.token, line_break.span.end_line, line_break.span.end_column), (&Token::LineBreak, 2, 1));
+    }
+
+    // the tokens of the source without the spaces between them
+    fn tokens(source: &str) -> Vec<Token> {
+        let (tokens, errors) = tokenize_with(source, TokenizerOptions::default());
+        assert!(errors.is_empty(), "{:?}", errors);
+        tokens.into_iter().map(|spanned| spanned.token).filter(|token| *token != Token::Space).collect()
+    }
 
+    #[test]
+    fn indents_and_dedents() {
+        use Token::{Dedent, Indent, LineBreak};
+        let name = |name: &str| Token::Identifier(name.to_string());
+        // blank and comment lines don't change the indentation, one line closes two blocks
+        let source = "a\n    b\n\n  # comment\n        c\nd\n";
+        assert_eq!(tokens(source), [name("a"), LineBreak, Indent, name("b"), LineBreak, Indent, name("c"), LineBreak, Dedent, Dedent, name("d"), LineBreak]);
+        // the blocks still open are closed at the end of the input, without a final newline too
+        assert_eq!(tokens("a\n\tb"), [name("a"), LineBreak, Indent, name("b"), LineBreak, Dedent]);
+        let (_, errors) = tokenize_with("a\n    b\n  c\n", TokenizerOptions::default());
+        assert_eq!(errors[0].kind, TokenizeErrorKind::BadDedent);
+    }
+
+    #[test]
+    fn fstring_parts() {
+        let [Token::FString(parts), Token::LineBreak] = &tokens("f'a{x!r:>{w}}b{y=}{{'\n")[..] else {
+            panic!("not a single f-string");
+        };
+        let [FStringPart::Literal(a), FStringPart::Expression(x), FStringPart::Literal(b), FStringPart::Expression(y), FStringPart::Literal(brace)] = &parts[..] else {
+            panic!("unexpected parts {:?}", parts);
+        };
+        assert_eq!((a.as_str(), b.as_str(), brace.as_str()), ("a", "b", "{"));
+        assert_eq!(x.tokens[0].token, Token::Identifier("x".to_string()));
+        // the spans of the embedded tokens point into the source
+        assert_eq!((x.tokens[0].span.start, x.conversion), (4, Some('r')));
+        let Some([FStringPart::Literal(align), FStringPart::Expression(width)]) = x.format_spec.as_deref() else {
+            panic!("unexpected format spec {:?}", x.format_spec);
+        };
+        assert_eq!((align.as_str(), &width.tokens[0].token), (">", &Token::Identifier("w".to_string())));
+        assert_eq!(y.debug_text.as_deref(), Some("y="));
+        let (_, errors) = tokenize_with("f'{x'\n", TokenizerOptions::default());
+        assert!(!errors.is_empty());
+    }
+
+    #[test]
+    fn comments_and_line_continuations() {
+        use Token::LineBreak;
+        let name = |name: &str| Token::Identifier(name.to_string());
+        let symbol = Token::Symbol;
+        let integer = |integer| Token::Number(Number::Integer(integer));
+        // a backslash or an open bracket joins the lines, a comment ends at the line break
+        let expected = [name("x"), symbol(Symbol::Assign), integer(1), symbol(Symbol::Plus), symbol(Symbol::LeftParen), integer(2), symbol(Symbol::RightParen), LineBreak];
+        assert_eq!(tokens("x = 1 + \\\n  (2 # two\n) # done\n"), expected);
+        // a line with only a comment is no logical line
+        assert_eq!(tokens("# only a comment\nx\n"), [name("x"), LineBreak]);
+        let options = TokenizerOptions { keep_comments: true, ..Default::default() };
+        let (kept, _) = tokenize_with("x # done\n", options);
+        assert!(kept.iter().any(|spanned| spanned.token == Token::Comment("# done".to_string())));
+        let (_, errors) = tokenize_with("x = \\ 1\n", TokenizerOptions::default());
+        assert_eq!(errors[0].message, "unexpected character after line continuation character");
+    }
+
+    #[test]
+    fn recoverable_errors() {
+        let source = "x = $ + 1\ny = 0b12\nz = 'open\n";
+        // without recovery the tokens stop at the first error
+        let (tokens, errors) = tokenize_with(source, TokenizerOptions::default());
+        assert_eq!(errors.len(), 1);
+        assert!(!tokens.iter().any(|spanned| spanned.token == Token::Identifier("y".to_string())));
+        let (tokens, errors) = tokenize_with(source, TokenizerOptions { recover_errors: true, ..Default::default() });
+        let messages: Vec<&str> = errors.iter().map(|error| error.message.as_str()).collect();
+        assert_eq!(messages, ["invalid character '$' (U+0024)", "invalid digit '2' in binary literal", "unterminated string literal"]);
+        assert_eq!((errors[0].span.line, errors[0].span.column, errors[0].character), (1, 5, Some('$')));
+        // the text around the errors is still tokenized
+        assert!(tokens.iter().any(|spanned| spanned.token == Token::Identifier("z".to_string())));
+    }
+
+    #[test]
+    fn operators() {
+        // every operator is read whole, not as a shorter one it starts with
+        for (text, symbol) in SYMBOLS.iter() {
+            assert_eq!(parse_symbol(text).as_ref(), Some(symbol), "{}", text);
+        }
+        let symbols = |source: &str| tokens(source).into_iter().filter_map(|token| match token {
+            Token::Symbol(symbol) => Some(reverse_symbol(&symbol)),
+            _ => None,
+        }).collect::<Vec<_>>();
+        assert_eq!(symbols("a **= b // c -> d := e != f <<= g >>= h ... ~i @ j\n"), ["**=", "//", "->", ":=", "!=", "<<=", ">>=", "...", "~", "@"]);
+        assert_eq!(symbols("x[1:-2] == y.z\n"), ["[", ":", "-", "]", "==", "."]);
+        let (_, errors) = tokenize_with("a ! b\n", TokenizerOptions::default());
+        assert_eq!(errors[0].message, "invalid character '!' (U+0021)");
+    }
+
+    #[test]
+    fn untokenize_round_trip() {
+        let source = "def f(a,\n      b = 2):  # comment\n\treturn a + \\\n  b\r\nx = f'{a!r:>{w}}' ; y = r'\\d' \n\n";
+        let options = TokenizerOptions { keep_trivia: true, ..Default::default() };
+        let (tokens, errors) = tokenize_with(source, options);
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(untokenize(&tokens), source);
+        // without the trivia the text is rebuilt from the tokens, it reads back the same
+        let (tokens, _) = tokenize_with(source, TokenizerOptions::default());
+        let rebuilt = untokenize(&tokens);
+        let (again, errors) = tokenize_with(&rebuilt, TokenizerOptions::default());
+        assert!(errors.is_empty(), "{:?}", errors);
+        // the spans inside the f-string moved, so the tokens are compared as text
+        let tokens = |tokens: Vec<SpannedToken>| tokens.iter().map(|spanned| token_text(&spanned.token)).collect::<Vec<_>>();
+        assert_eq!(tokens(again), tokens(tokenize_with(source, TokenizerOptions::default()).0));
+    }
+
+    #[test]
+    fn unicode_identifiers() {
+        let name = |name: &str| Token::Identifier(name.to_string());
+        assert_eq!(tokens("naïve = π_2\n")[..3], [name("naïve"), Token::Symbol(Symbol::Assign), name("π_2")]);
+        // identifiers are NFKC normalized, like in Python
+        assert_eq!(tokens("ﬁx\n")[0], name("fix"));
+        let (tokens, errors) = tokenize_with("x€ = 1\n", TokenizerOptions { recover_errors: true, ..Default::default() });
+        assert_eq!(tokens[0].token, name("x"));
+        assert_eq!((errors[0].message.as_str(), errors[0].span.column), ("invalid character '€' (U+20AC)", 2));
+    }
+
+    #[test]
+    fn string_literals() {
+        let string = |text: &str| Token::String(text.to_string());
+        assert_eq!(tokens("'a\\tb\\x41\\101\\u00e9\\N{BULLET}\\q'\n")[0], string("a\tbAAé•\\q"));
+        assert_eq!(tokens("r'\\n' Rb'\\x00' b'\\x00'\n")[..3], [string("\\n"), Token::Bytes(b"\\x00".to_vec()), Token::Bytes(vec![0])]);
+        // triple-quoted strings span lines, a backslash at the end of a line joins it to the next
+        assert_eq!(tokens("'''a\n\"b\"''' \"x\\\ny\"\n")[..2], [string("a\n\"b\""), string("xy")]);
+        let error = |source: &str| tokenize_with(source, TokenizerOptions::default()).1[0].message.clone();
+        assert_eq!(error("b'é'\n"), "bytes can only contain ASCII literal characters");
+        assert_eq!(error("'\\x4'\n"), "truncated \\xXX escape");
+    }
+}